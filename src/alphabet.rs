@@ -0,0 +1,519 @@
+//! Tables translating dit/dah patterns into the characters of different scripts.
+
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::morse_decoder::{Code, LETTERS, SYMBOLS};
+
+/// Russian Cyrillic letters.
+const RUSSIAN: [(&str, &str); 32] = [
+    (".-", "А"),
+    ("-...", "Б"),
+    (".--", "В"),
+    ("--.", "Г"),
+    ("-..", "Д"),
+    (".", "Е"),
+    ("...-", "Ж"),
+    ("--..", "З"),
+    ("..", "И"),
+    (".---", "Й"),
+    ("-.-", "К"),
+    (".-..", "Л"),
+    ("--", "М"),
+    ("-.", "Н"),
+    ("---", "О"),
+    (".--.", "П"),
+    (".-.", "Р"),
+    ("...", "С"),
+    ("-", "Т"),
+    ("..-", "У"),
+    ("..-.", "Ф"),
+    ("....", "Х"),
+    ("-.-.", "Ц"),
+    ("---.", "Ч"),
+    ("----", "Ш"),
+    ("--.-", "Щ"),
+    ("--.--", "Ъ"),
+    ("-.--", "Ы"),
+    ("-..-", "Ь"),
+    ("..-..", "Э"),
+    ("..--", "Ю"),
+    (".-.-", "Я"),
+];
+
+/// Greek letters and diphthongs.
+const GREEK: [(&str, &str); 30] = [
+    (".-", "Α"),
+    ("-...", "Β"),
+    ("--.", "Γ"),
+    ("-..", "Δ"),
+    (".", "Ε"),
+    ("--..", "Ζ"),
+    ("....", "Η"),
+    ("-.-.", "Θ"),
+    ("..", "Ι"),
+    ("-.-", "Κ"),
+    (".-..", "Λ"),
+    ("--", "Μ"),
+    ("-.", "Ν"),
+    ("-..-", "Ξ"),
+    ("---", "Ο"),
+    (".--.", "Π"),
+    (".-.", "Ρ"),
+    ("...", "Σ"),
+    ("-", "Τ"),
+    ("-.--", "Υ"),
+    ("..-.", "Φ"),
+    ("----", "Χ"),
+    ("--.-", "Ψ"),
+    (".--", "Ω"),
+    (".-.-", "ΑΙ"),
+    ("..--", "ΑΥ"),
+    ("...-", "ΕΥ"),
+    ("---.", "ΟΙ"),
+    ("..-", "ΟΥ"),
+    (".---", "ΥΙ"),
+];
+
+/// Japanese Wabun code in katakana.
+const WABUN: [(&str, &str); 56] = [
+    (".-", "イ"),
+    (".-.-", "ロ"),
+    ("-...", "ハ"),
+    ("-.-.", "ニ"),
+    ("-..", "ホ"),
+    (".", "ヘ"),
+    ("..-..", "ト"),
+    ("..-.", "チ"),
+    ("--.", "リ"),
+    ("....", "ヌ"),
+    ("-.--.", "ル"),
+    (".---", "ヲ"),
+    ("-.-", "ワ"),
+    (".-..", "カ"),
+    ("--", "ヨ"),
+    ("-.", "タ"),
+    ("---", "レ"),
+    ("---.", "ソ"),
+    (".--.", "ツ"),
+    ("--.-", "ネ"),
+    (".-.", "ナ"),
+    ("...", "ラ"),
+    ("-", "ム"),
+    ("..-", "ウ"),
+    (".-..-", "ヰ"),
+    ("..--", "ノ"),
+    (".-...", "オ"),
+    ("...-", "ク"),
+    (".--", "ヤ"),
+    ("-..-", "マ"),
+    ("-.--", "ケ"),
+    ("--..", "フ"),
+    ("----", "コ"),
+    ("-.---", "エ"),
+    (".-.--", "テ"),
+    ("--.--", "ア"),
+    ("-.-.-", "サ"),
+    ("-.-..", "キ"),
+    ("-..--", "ユ"),
+    ("-...-", "メ"),
+    ("..-.-", "ミ"),
+    ("--.-.", "シ"),
+    (".--..", "ヱ"),
+    ("--..-", "ヒ"),
+    ("-..-.", "モ"),
+    (".---.", "セ"),
+    ("---.-", "ス"),
+    (".-.-.", "ン"),
+    ("..", "゛"),
+    ("..--.", "゜"),
+    (".--.-", "ー"),
+    (".-.-.-", "、"),
+    (".-.-..", "」"),
+    ("-.--.-", "（"),
+    (".-..-.", "）"),
+    ("-.-.--", "「"),
+];
+
+/// Prosign DO, which switches from Latin to Wabun.
+const WABUN_START: &str = "-..---";
+/// Prosign SN, which switches from Wabun back to Latin.
+const WABUN_END: &str = "...-.";
+
+/// Hebrew letters.
+const HEBREW: [(&str, &str); 22] = [
+    (".-", "א"),
+    ("-...", "ב"),
+    ("--.", "ג"),
+    ("-..", "ד"),
+    ("---", "ה"),
+    (".", "ו"),
+    ("--..", "ז"),
+    ("....", "ח"),
+    ("..-", "ט"),
+    ("..", "י"),
+    ("-.-", "כ"),
+    (".-..", "ל"),
+    ("--", "מ"),
+    ("-.", "נ"),
+    ("-.-.", "ס"),
+    (".---", "ע"),
+    (".--.", "פ"),
+    (".--", "צ"),
+    ("--.-", "ק"),
+    (".-.", "ר"),
+    ("...", "ש"),
+    ("-", "ת"),
+];
+
+/// Arabic letters.
+const ARABIC: [(&str, &str); 29] = [
+    (".-", "ا"),
+    ("-...", "ب"),
+    ("-", "ت"),
+    ("-.-.", "ث"),
+    (".---", "ج"),
+    ("....", "ح"),
+    ("---", "خ"),
+    ("-..", "د"),
+    ("--..", "ذ"),
+    (".-.", "ر"),
+    ("---.", "ز"),
+    ("...", "س"),
+    ("----", "ش"),
+    ("-..-", "ص"),
+    ("...-", "ض"),
+    ("..-", "ط"),
+    ("-.--", "ظ"),
+    (".-.-", "ع"),
+    ("--.", "غ"),
+    ("..-.", "ف"),
+    ("--.-", "ق"),
+    ("-.-", "ك"),
+    (".-..", "ل"),
+    ("--", "م"),
+    ("-.", "ن"),
+    ("..-..", "ه"),
+    (".--", "و"),
+    ("..", "ي"),
+    (".", "ء"),
+];
+
+/// Korean Hangul jamo in the Standard Korean Alphabet Transliteration System (SKATS).
+const KOREAN: [(&str, &str); 26] = [
+    (".-..", "ㄱ"),
+    ("..-.", "ㄴ"),
+    ("-...", "ㄷ"),
+    ("...-", "ㄹ"),
+    ("--", "ㅁ"),
+    (".--", "ㅂ"),
+    ("--.", "ㅅ"),
+    ("-.-", "ㅇ"),
+    (".--.", "ㅈ"),
+    ("-.-.", "ㅊ"),
+    ("-..-", "ㅋ"),
+    ("--..", "ㅌ"),
+    ("---", "ㅍ"),
+    (".---", "ㅎ"),
+    (".", "ㅏ"),
+    ("..", "ㅑ"),
+    ("-", "ㅓ"),
+    ("...", "ㅕ"),
+    (".-", "ㅗ"),
+    ("-.", "ㅛ"),
+    ("....", "ㅜ"),
+    (".-.", "ㅠ"),
+    ("-..", "ㅡ"),
+    ("..-", "ㅣ"),
+    ("--.-", "ㅐ"),
+    ("-.--", "ㅔ"),
+];
+
+/// American (railroad) Morse, see [`Code::pattern`] for the notation of long dashes and
+/// spaces within characters.
+const AMERICAN: [(&str, &str); 40] = [
+    (".-", "A"),
+    ("-...", "B"),
+    (".. .", "C"),
+    ("-..", "D"),
+    (".", "E"),
+    (".-.", "F"),
+    ("--.", "G"),
+    ("....", "H"),
+    ("..", "I"),
+    ("-.-.", "J"),
+    ("-.-", "K"),
+    ("_", "L"),
+    ("--", "M"),
+    ("-.", "N"),
+    (". .", "O"),
+    (".....", "P"),
+    ("..-.", "Q"),
+    (". ..", "R"),
+    ("...", "S"),
+    ("-", "T"),
+    ("..-", "U"),
+    ("...-", "V"),
+    (".--", "W"),
+    (".-..", "X"),
+    (".. ..", "Y"),
+    ("... .", "Z"),
+    (".--.", "1"),
+    ("..-..", "2"),
+    ("...-.", "3"),
+    ("....-", "4"),
+    ("---", "5"),
+    ("......", "6"),
+    ("--..", "7"),
+    ("-....", "8"),
+    ("-..-", "9"),
+    ("=", "0"),
+    ("..--..", "."),
+    (".-.-", ","),
+    ("-..-.", "?"),
+    (". ...", "&"),
+];
+
+/// Which alphabet the decoder translates into, as saved in the configuration.
+#[derive(Serialize, Deserialize, Clone, PartialEq, Default, Debug)]
+#[serde(tag = "kind")]
+pub enum AlphabetSettings {
+    /// ITU letters, digits and punctuation.
+    #[default]
+    Latin,
+    /// Russian Cyrillic.
+    Russian,
+    /// Greek.
+    Greek,
+    /// Japanese Wabun in katakana. Like on the air, decoding starts in Latin until the
+    /// prosign DO switches to Wabun, and SN switches back.
+    Wabun,
+    /// Hebrew.
+    Hebrew,
+    /// Arabic.
+    Arabic,
+    /// Korean SKATS.
+    Korean,
+    /// American (railroad) Morse, decoded with the timing model of
+    /// [`crate::morse_decoder::AmericanTiming`].
+    American,
+    /// A table read from a TOML file, see [`Alphabet`].
+    Custom {
+        /// Path of the TOML file.
+        path: String,
+    },
+}
+
+impl AlphabetSettings {
+    /// The built-in alphabets and their names.
+    pub const BUILT_IN: [(AlphabetSettings, &'static str); 8] = [
+        (Self::Latin, "ITU Latin"),
+        (Self::Russian, "Russian Cyrillic"),
+        (Self::Greek, "Greek"),
+        (Self::Wabun, "Japanese Wabun"),
+        (Self::Hebrew, "Hebrew"),
+        (Self::Arabic, "Arabic"),
+        (Self::Korean, "Korean SKATS"),
+        (Self::American, "American Morse"),
+    ];
+
+    /// Name of the alphabet, or the path of a custom table.
+    pub fn name(&self) -> &str {
+        match self {
+            Self::Custom { path } => path,
+            _ => Self::BUILT_IN
+                .iter()
+                .find_map(|(alphabet, name)| (alphabet == self).then_some(*name))
+                .unwrap_or_default(),
+        }
+    }
+
+    /// Build the table of a built-in alphabet or read a custom one.
+    /// The other scripts include the ITU digits and punctuation whose patterns are not
+    /// letters, while American Morse has its own.
+    pub fn load(&self) -> Result<Alphabet, String> {
+        Ok(match self {
+            Self::Latin => Alphabet::latin(),
+            Self::Russian => Alphabet::with_symbols(&RUSSIAN),
+            Self::Greek => Alphabet::with_symbols(&GREEK),
+            Self::Wabun => Alphabet {
+                shift: Some(Shift {
+                    enter: WABUN_START.to_owned(),
+                    leave: WABUN_END.to_owned(),
+                    characters: Alphabet::with_symbols(&WABUN).characters,
+                }),
+                ..Alphabet::latin()
+            },
+            Self::Hebrew => Alphabet::with_symbols(&HEBREW),
+            Self::Arabic => Alphabet::with_symbols(&ARABIC),
+            Self::Korean => Alphabet::with_symbols(&KOREAN),
+            Self::American => Alphabet {
+                characters: AMERICAN
+                    .iter()
+                    .map(|(pattern, text)| (pattern.to_string(), text.to_string()))
+                    .collect(),
+                shift: None,
+            },
+            #[cfg(feature = "custom-alphabets")]
+            Self::Custom { path } => Alphabet::read(path.as_ref())?,
+            #[cfg(not(feature = "custom-alphabets"))]
+            Self::Custom { .. } => {
+                return Err("Custom alphabets require the custom-alphabets feature.".to_owned())
+            }
+        })
+    }
+}
+
+/// The characters of an alphabet by their dit/dah pattern.
+///
+/// Custom alphabets are TOML files with a `characters` table of patterns of `.` and `-`,
+/// and optionally a second table to switch to and from with prosigns:
+///
+/// ```toml
+/// [characters]
+/// ".-" = "A"
+/// "-..." = "B"
+///
+/// [shift]
+/// enter = "-..---"
+/// leave = "...-."
+/// [shift.characters]
+/// ".-" = "イ"
+/// ```
+#[derive(Deserialize, Clone, PartialEq, Default, Debug)]
+pub struct Alphabet {
+    /// Text of each pattern.
+    pub characters: BTreeMap<String, String>,
+    /// Second table and the patterns that switch to and from it.
+    #[serde(default)]
+    pub shift: Option<Shift>,
+}
+
+/// A second table of an [`Alphabet`], such as the katakana of Wabun.
+#[derive(Deserialize, Clone, PartialEq, Default, Debug)]
+pub struct Shift {
+    /// Pattern that switches to this table.
+    pub enter: String,
+    /// Pattern that switches back to the main table.
+    pub leave: String,
+    /// Text of each pattern.
+    pub characters: BTreeMap<String, String>,
+}
+
+impl Alphabet {
+    /// ITU letters, digits and punctuation.
+    pub fn latin() -> Self {
+        Self {
+            characters: LETTERS
+                .iter()
+                .chain(&SYMBOLS)
+                .map(|(pattern, character)| (pattern.to_string(), character.to_string()))
+                .collect(),
+            shift: None,
+        }
+    }
+
+    /// `letters` together with the ITU digits and punctuation whose patterns they do not use.
+    fn with_symbols(letters: &[(&str, &str)]) -> Self {
+        let mut characters: BTreeMap<String, String> = SYMBOLS
+            .iter()
+            .map(|(pattern, character)| (pattern.to_string(), character.to_string()))
+            .collect();
+        characters.extend(
+            letters
+                .iter()
+                .map(|(pattern, text)| (pattern.to_string(), text.to_string())),
+        );
+        Self {
+            characters,
+            shift: None,
+        }
+    }
+
+    /// Read a custom alphabet from a TOML file.
+    #[cfg(feature = "custom-alphabets")]
+    pub fn read(path: &std::path::Path) -> Result<Self, String> {
+        let text = std::fs::read_to_string(path)
+            .map_err(|error| format!("Unable to read {}: {error}.", path.display()))?;
+        let alphabet: Self = toml::from_str(&text)
+            .map_err(|error| format!("Invalid alphabet {}: {error}", path.display()))?;
+        let shift = alphabet.shift.iter().flat_map(|shift| {
+            [&shift.enter, &shift.leave]
+                .into_iter()
+                .chain(shift.characters.keys())
+        });
+        if let Some(pattern) =
+            alphabet.characters.keys().chain(shift).find(|pattern| {
+                pattern.is_empty() || !pattern.chars().all(|x| x == '.' || x == '-')
+            })
+        {
+            return Err(format!(
+                "Invalid pattern \"{pattern}\" in {}, use only . and -.",
+                path.display()
+            ));
+        }
+        Ok(alphabet)
+    }
+
+    /// Patterns of the characters of the main table, or the second table if `shifted`,
+    /// and the patterns that switch between them.
+    pub fn patterns(&self, shifted: bool) -> impl Iterator<Item = &str> {
+        let characters = match &self.shift {
+            Some(shift) if shifted => &shift.characters,
+            _ => &self.characters,
+        };
+        characters
+            .keys()
+            .chain(
+                self.shift
+                    .iter()
+                    .flat_map(|shift| [&shift.enter, &shift.leave]),
+            )
+            .map(String::as_str)
+    }
+
+    /// The pattern of `text` in the main table, or the second table if `shifted`.
+    pub fn find(&self, text: &str, shifted: bool) -> Option<&str> {
+        let characters = match &self.shift {
+            Some(shift) if shifted => &shift.characters,
+            _ => &self.characters,
+        };
+        characters
+            .iter()
+            .find_map(|(pattern, x)| (x == text).then_some(pattern.as_str()))
+    }
+
+    /// Whether a character switches tables: `Some(true)` to switch to the second table
+    /// from the main table, and `Some(false)` to switch back while `shifted`. Other
+    /// characters, including the pattern that switches to the current table, are
+    /// translated normally.
+    pub fn switch(&self, character: &[Code], shifted: bool) -> Option<bool> {
+        let shift = self.shift.as_ref()?;
+        let pattern = Code::pattern(character);
+        let switch = if shifted { &shift.leave } else { &shift.enter };
+        (pattern == *switch).then_some(!shifted)
+    }
+
+    /// Translate a single character's dits and dahs with the main table, or the second
+    /// table if `shifted`, rendering prosigns as `<AR>`. Prosigns that share their
+    /// pattern with a character are only used if `prefer_prosigns` is set. Returns
+    /// [`Code::UNKNOWN_CHARACTER`] for other patterns.
+    pub fn translate(&self, character: &[Code], shifted: bool, prefer_prosigns: bool) -> String {
+        let prosign = || Code::translate_prosign(character).map(|prosign| format!("<{prosign}>"));
+        if prefer_prosigns {
+            if let Some(prosign) = prosign() {
+                return prosign;
+            }
+        }
+        let characters = match &self.shift {
+            Some(shift) if shifted => &shift.characters,
+            _ => &self.characters,
+        };
+        characters
+            .get(&Code::pattern(character))
+            .cloned()
+            .or_else(prosign)
+            .unwrap_or_else(|| Code::UNKNOWN_CHARACTER.to_string())
+    }
+}
@@ -0,0 +1,385 @@
+//! Detection of a tone in audio, for decoding Morse code from recordings, and synthesis
+//! of Morse code audio.
+
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use crate::morse_encoder::Schedule;
+
+/// How the tone of a signal is detected.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+pub struct ToneSettings {
+    /// Frequency of the tone (Hz).
+    pub frequency: f32,
+    /// Length of each analysis block (ms). Shorter blocks follow faster code, longer
+    /// blocks reject more noise and neighbouring signals.
+    pub block: f32,
+    /// Minimum level for the tone to switch on, from the noise floor (0) to the loudest
+    /// recent block (1).
+    pub on_threshold: f32,
+    /// Relative level below which the tone switches back off.
+    /// Set below [`ToneSettings::on_threshold`] for hysteresis, clamped to at most it.
+    pub off_threshold: f32,
+    /// Minimum amplitude of the tone (0-1 of full scale) to switch on, so silence
+    /// is not amplified into a signal.
+    pub squelch: f32,
+}
+
+impl Default for ToneSettings {
+    fn default() -> Self {
+        Self {
+            frequency: 700.0,
+            block: 10.0,
+            on_threshold: 0.5,
+            off_threshold: 0.3,
+            squelch: 0.01,
+        }
+    }
+}
+
+/// The level of the tone in one block of audio.
+#[derive(Clone, Copy, Debug)]
+pub struct ToneSample {
+    /// Time of the end of the block from the start of the audio.
+    pub time: Duration,
+    /// Amplitude of the tone (0-1 of full scale).
+    pub amplitude: f32,
+    /// Amplitude from the noise floor (0) to the loudest recent block (1).
+    pub level: f32,
+    /// Whether the tone is classified as present.
+    pub on: bool,
+}
+
+/// Measures the amplitude of a tone with a Goertzel filter, block by block, and
+/// classifies it as on or off with hysteresis. The level is measured between the
+/// average amplitude while off (the noise floor) and the slowly decaying peak, so the
+/// detector follows fading signals.
+pub struct ToneDetector {
+    settings: ToneSettings,
+    sample_rate: u32,
+    block: Vec<f32>,
+    /// Number of samples processed, for timestamps.
+    samples: u64,
+    /// Slowly decaying maximum amplitude.
+    peak: f32,
+    /// Average amplitude while off, [`None`] before the first block.
+    noise: Option<f32>,
+    on: bool,
+}
+
+impl ToneDetector {
+    /// Time for the reference peak amplitude to fall to 1/e when the signal fades.
+    pub const PEAK_DECAY: f32 = 5.0;
+    /// Time constant (s) of the noise floor average.
+    pub const NOISE_AVERAGE: f32 = 0.5;
+    /// Minimum ratio of the peak to the noise floor for any tone to be detected.
+    pub const MIN_CONTRAST: f32 = 4.0;
+
+    /// Create a detector for audio with `sample_rate` samples per second.
+    pub fn new(settings: ToneSettings, sample_rate: u32) -> Self {
+        Self {
+            settings,
+            sample_rate,
+            block: Vec::new(),
+            samples: 0,
+            peak: 0.0,
+            noise: None,
+            on: false,
+        }
+    }
+
+    /// Number of audio samples in one analysis block.
+    pub fn block_length(&self) -> usize {
+        ((self.settings.block / 1000.0 * self.sample_rate as f32).round() as usize).max(1)
+    }
+
+    /// Process mono samples in the range -1 to 1 and return the level of every
+    /// block completed by them.
+    pub fn process(&mut self, samples: &[f32]) -> Vec<ToneSample> {
+        let length = self.block_length();
+        let mut results = Vec::new();
+        for &sample in samples {
+            self.block.push(sample);
+            self.samples += 1;
+            if self.block.len() < length {
+                continue;
+            }
+
+            let amplitude = goertzel(&self.block, self.settings.frequency, self.sample_rate);
+            self.block.clear();
+            let seconds = length as f32 / self.sample_rate as f32;
+            let noise = *self.noise.get_or_insert(amplitude);
+            self.peak = amplitude.max(self.peak * (-seconds / Self::PEAK_DECAY).exp());
+
+            let level = if self.peak > noise {
+                ((amplitude - noise) / (self.peak - noise)).clamp(0.0, 1.0)
+            } else {
+                0.0
+            };
+            self.on = amplitude >= self.settings.squelch
+                && self.peak >= noise * Self::MIN_CONTRAST
+                && if self.on {
+                    level >= self.settings.off_threshold.min(self.settings.on_threshold)
+                } else {
+                    level >= self.settings.on_threshold
+                };
+            if !self.on {
+                let weight = 1.0 - (-seconds / Self::NOISE_AVERAGE).exp();
+                self.noise = Some(noise + (amplitude - noise) * weight);
+            }
+            results.push(ToneSample {
+                time: Duration::from_secs_f64(self.samples as f64 / self.sample_rate as f64),
+                amplitude,
+                level,
+                on: self.on,
+            });
+        }
+        results
+    }
+}
+
+/// Amplitude of the `frequency` component of `samples` using the Goertzel algorithm.
+/// The samples are weighted with a Hann window, so that other tones do not leak into
+/// the amplitude through the edges of the block.
+pub fn goertzel(samples: &[f32], frequency: f32, sample_rate: u32) -> f32 {
+    if samples.len() < 2 {
+        return 0.0;
+    }
+    let step = std::f32::consts::TAU / samples.len() as f32;
+    let coefficient = 2.0 * (std::f32::consts::TAU * frequency / sample_rate as f32).cos();
+    let (mut s1, mut s2) = (0.0, 0.0);
+    for (i, sample) in samples.iter().enumerate() {
+        let window = 0.5 - 0.5 * (step * i as f32).cos();
+        let s = sample * window + coefficient * s1 - s2;
+        s2 = s1;
+        s1 = s;
+    }
+    let power = (s1 * s1 + s2 * s2 - coefficient * s1 * s2).max(0.0);
+    // The window halves the sum of the samples
+    4.0 * power.sqrt() / samples.len() as f32
+}
+
+/// How Morse code audio is generated.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+pub struct SynthesisSettings {
+    /// Frequency of the tone (Hz).
+    pub frequency: f32,
+    /// Samples per second of the generated audio.
+    pub sample_rate: u32,
+    /// Peak amplitude of the tone (0-1 of full scale).
+    pub amplitude: f32,
+    /// Length of the raised cosine rise and fall of each mark (ms), which avoids key
+    /// clicks. Limited to half of the mark.
+    pub rise: f32,
+    /// Standard deviation of added white noise (0-1 of full scale), 0 for none.
+    pub noise: f32,
+}
+
+impl Default for SynthesisSettings {
+    fn default() -> Self {
+        Self {
+            frequency: 700.0,
+            sample_rate: 8000,
+            amplitude: 0.5,
+            rise: 5.0,
+            noise: 0.0,
+        }
+    }
+}
+
+/// Silence before and after synthesized audio, so players and detectors do not cut off
+/// the first or last mark.
+pub const SYNTHESIS_PADDING: std::time::Duration = std::time::Duration::from_millis(500);
+
+/// Generate mono audio in the range -1 to 1 of the schedule's marks as a shaped tone,
+/// padded with [`SYNTHESIS_PADDING`] of silence.
+pub fn synthesize(schedule: &Schedule, settings: &SynthesisSettings) -> Vec<f32> {
+    let rate = settings.sample_rate as f64;
+    let step = std::f64::consts::TAU * settings.frequency as f64 / rate;
+    let mut samples = Vec::new();
+    let mut noise = Noise::new(0x2545_f491_4f6c_dd1d);
+    let padding = (false, SYNTHESIS_PADDING);
+    for (on, duration) in std::iter::once(&padding)
+        .chain(&schedule.elements)
+        .chain(std::iter::once(&padding))
+    {
+        let length = (duration.as_secs_f64() * rate).round() as usize;
+        let rise = ((settings.rise as f64 / 1000.0 * rate) as usize).min(length / 2);
+        for i in 0..length {
+            let mut sample = 0.0;
+            if *on {
+                // Raised cosine envelope at both ends of the mark
+                let edge = i.min(length - 1 - i);
+                let envelope = if edge < rise {
+                    0.5 - 0.5 * (std::f64::consts::PI * edge as f64 / rise as f64).cos()
+                } else {
+                    1.0
+                };
+                let phase = step * samples.len() as f64;
+                sample = (settings.amplitude as f64 * envelope * phase.sin()) as f32;
+            }
+            if settings.noise > 0.0 {
+                sample += settings.noise * noise.gaussian();
+            }
+            samples.push(sample.clamp(-1.0, 1.0));
+        }
+    }
+    samples
+}
+
+/// Deterministic pseudorandom numbers (xorshift64*) for generated noise.
+struct Noise(u64);
+
+impl Noise {
+    fn new(seed: u64) -> Self {
+        Self(seed.max(1))
+    }
+
+    /// Uniformly distributed in 0-1.
+    fn uniform(&mut self) -> f32 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        (self.0.wrapping_mul(0x2545_f491_4f6c_dd1d) >> 40) as f32 / (1u64 << 24) as f32
+    }
+
+    /// Approximately normally distributed with a standard deviation of 1.
+    fn gaussian(&mut self) -> f32 {
+        // Sum of 12 uniforms has a variance of 1.
+        (0..12).map(|_| self.uniform()).sum::<f32>() - 6.0
+    }
+}
+
+/// Write mono samples in the range -1 to 1 to a 16 bit WAV file.
+#[cfg(feature = "wav")]
+pub fn write_wav(path: &std::path::Path, samples: &[f32], sample_rate: u32) -> Result<(), String> {
+    let error = |error: hound::Error| format!("Unable to write {}: {error}.", path.display());
+    let spec = hound::WavSpec {
+        channels: 1,
+        sample_rate,
+        bits_per_sample: 16,
+        sample_format: hound::SampleFormat::Int,
+    };
+    let mut writer = hound::WavWriter::create(path, spec).map_err(error)?;
+    for sample in samples {
+        writer
+            .write_sample((sample.clamp(-1.0, 1.0) * i16::MAX as f32) as i16)
+            .map_err(error)?;
+    }
+    writer.finalize().map_err(error)
+}
+
+/// Read a WAV file, mixing its channels to mono samples in the range -1 to 1.
+/// Returns the samples and the sample rate.
+#[cfg(feature = "wav")]
+pub fn read_wav(path: &std::path::Path) -> Result<(Vec<f32>, u32), String> {
+    let error = |error: hound::Error| format!("Unable to read {}: {error}.", path.display());
+    let mut reader = hound::WavReader::open(path).map_err(error)?;
+    let spec = reader.spec();
+    let samples: Vec<f32> = match spec.sample_format {
+        hound::SampleFormat::Float => reader.samples::<f32>().collect::<Result<_, _>>(),
+        hound::SampleFormat::Int => {
+            let scale = (1u64 << (spec.bits_per_sample - 1)) as f32;
+            reader
+                .samples::<i32>()
+                .map(|sample| sample.map(|sample| sample as f32 / scale))
+                .collect::<Result<_, _>>()
+        }
+    }
+    .map_err(error)?;
+    let channels = spec.channels.max(1) as usize;
+    let mono = samples
+        .chunks(channels)
+        .map(|frame| frame.iter().sum::<f32>() / channels as f32)
+        .collect();
+    Ok((mono, spec.sample_rate))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        alphabet::Alphabet,
+        morse_decoder::{DecoderSettings, MorseDecoder},
+        morse_encoder::EncoderSettings,
+    };
+
+    /// Detect the tone of `samples` and decode it at 20 WPM.
+    fn decode(samples: &[f32], tone: ToneSettings, sample_rate: u32) -> String {
+        let settings = DecoderSettings {
+            dit_dah: 120,
+            letter: 120,
+            letter_word: 300,
+            ..DecoderSettings::default()
+        };
+        let mut detector = ToneDetector::new(tone, sample_rate);
+        let mut decoder = MorseDecoder::new();
+        let mut end = Duration::ZERO;
+        for sample in detector.process(samples) {
+            decoder.sample(sample.on, sample.time, &settings);
+            end = sample.time;
+        }
+        decoder.finish(end, &settings);
+        decoder.transcript().trim().to_owned()
+    }
+
+    #[test]
+    fn tone_round_trip() {
+        let text = "CQ TEST DE W1AW K";
+        let schedule = Schedule::from_text(text, &Alphabet::latin(), &EncoderSettings::default());
+        for noise in [0.0, 0.1, 0.3] {
+            let settings = SynthesisSettings {
+                noise,
+                ..SynthesisSettings::default()
+            };
+            let samples = synthesize(&schedule, &settings);
+            let decoded = decode(&samples, ToneSettings::default(), settings.sample_rate);
+            assert_eq!(decoded, text, "noise {noise}");
+        }
+    }
+
+    #[test]
+    fn other_tones_stay_off() {
+        let schedule = Schedule::from_text("TEST", &Alphabet::latin(), &EncoderSettings::default());
+        for (frequency, noise) in [(300.0, 0.0), (1100.0, 0.0), (400.0, 0.05), (1000.0, 0.05)] {
+            let settings = SynthesisSettings {
+                frequency,
+                noise,
+                ..SynthesisSettings::default()
+            };
+            let samples = synthesize(&schedule, &settings);
+            let mut detector = ToneDetector::new(ToneSettings::default(), settings.sample_rate);
+            let on = detector.process(&samples).iter().filter(|x| x.on).count();
+            assert_eq!(on, 0, "{frequency} Hz with noise {noise}");
+        }
+    }
+
+    #[test]
+    fn goertzel_amplitude() {
+        let sample_rate = 8000;
+        let tone: Vec<f32> = (0..800)
+            .map(|i| 0.5 * (std::f32::consts::TAU * 700.0 * i as f32 / sample_rate as f32).sin())
+            .collect();
+        assert!((goertzel(&tone, 700.0, sample_rate) - 0.5).abs() < 0.01);
+        assert!(goertzel(&tone, 1000.0, sample_rate) < 0.01);
+        assert_eq!(goertzel(&[], 700.0, sample_rate), 0.0);
+    }
+
+    #[cfg(feature = "wav")]
+    #[test]
+    fn wav_round_trip() {
+        let path = std::env::temp_dir().join(format!("morse-test-{}.wav", std::process::id()));
+        let samples = [0.0, 0.5, -0.5, 1.0, -1.0, 0.25];
+        write_wav(&path, &samples, 8000).unwrap();
+        let read = read_wav(&path);
+        std::fs::remove_file(&path).unwrap();
+        let (read, sample_rate) = read.unwrap();
+        assert_eq!(sample_rate, 8000);
+        assert_eq!(read.len(), samples.len());
+        for (a, b) in read.iter().zip(samples) {
+            assert!((a - b).abs() < 1e-4, "{read:?}");
+        }
+        assert!(read_wav(&path).is_err());
+    }
+}
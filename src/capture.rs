@@ -0,0 +1,185 @@
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::{self, Receiver, Sender},
+        Arc, Mutex,
+    },
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+
+use morse::{
+    sensor::Reading,
+    signal::{SignalSource, SourceChannel},
+};
+
+/// Settings read by the capture thread before every sample.
+#[derive(Clone)]
+pub struct CaptureSettings {
+    pub channels: Vec<SourceChannel>,
+    /// Target samples per second of live sources.
+    pub sample_rate: f32,
+}
+
+pub enum CaptureMessage {
+    /// The channel's signal changed state at `time`.
+    Transition {
+        channel: usize,
+        time: Duration,
+        on: bool,
+    },
+    /// Sent every [`Capture::STATUS_INTERVAL`] with the latest sample of each channel
+    /// and sampling statistics.
+    Status {
+        time: Duration,
+        readings: Vec<(bool, Reading)>,
+        /// Achieved samples per second.
+        sample_rate: f32,
+        /// Standard deviation of the sample interval (ms).
+        jitter: f32,
+    },
+    /// A recorded source has ended after `time` and the thread has stopped.
+    End { time: Duration },
+    /// Capturing failed and the thread has stopped.
+    Error(String),
+}
+
+/// Samples a [`SignalSource`] on a background thread, independent of the UI frame rate.
+/// Sample times are measured from `epoch`. Recorded sources start at the current time
+/// and are played back at their recorded speed.
+pub struct Capture {
+    pub settings: Arc<Mutex<CaptureSettings>>,
+    pub receiver: Receiver<CaptureMessage>,
+    stop: Arc<AtomicBool>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl Capture {
+    pub const STATUS_INTERVAL: Duration = Duration::from_millis(50);
+
+    pub fn start(source: Box<dyn SignalSource>, settings: CaptureSettings, epoch: Instant) -> Self {
+        let settings = Arc::new(Mutex::new(settings));
+        let stop = Arc::new(AtomicBool::new(false));
+        let (sender, receiver) = mpsc::channel();
+        let handle = {
+            let settings = settings.clone();
+            let stop = stop.clone();
+            thread::spawn(move || Self::run(source, settings, epoch, stop, sender))
+        };
+        Self {
+            settings,
+            receiver,
+            stop,
+            handle: Some(handle),
+        }
+    }
+
+    fn run(
+        mut source: Box<dyn SignalSource>,
+        settings: Arc<Mutex<CaptureSettings>>,
+        epoch: Instant,
+        stop: Arc<AtomicBool>,
+        sender: Sender<CaptureMessage>,
+    ) {
+        let mut last_on: Vec<Option<bool>> = Vec::new();
+        let mut deadline = Instant::now();
+        let mut last_sample: Option<Duration> = None;
+        let mut last_status = Duration::ZERO;
+        let mut intervals: Vec<f32> = Vec::new();
+        let live = source.live();
+        let start = Instant::now();
+        let offset = if live {
+            Duration::ZERO
+        } else {
+            epoch.elapsed()
+        };
+
+        while !stop.load(Ordering::Relaxed) {
+            let settings = settings.lock().unwrap().clone();
+            let (recorded, readings) = match source.next_sample(&settings.channels) {
+                Ok(Some(sample)) => (sample.time, sample.readings),
+                Ok(None) => {
+                    let time = last_sample.unwrap_or(offset);
+                    let _ = sender.send(CaptureMessage::End { time });
+                    return;
+                }
+                Err(error) => {
+                    let _ = sender.send(CaptureMessage::Error(error));
+                    return;
+                }
+            };
+            let time = offset + recorded;
+            last_on.resize(readings.len(), None);
+
+            if let Some(last_sample) = last_sample {
+                intervals.push(time.saturating_sub(last_sample).as_secs_f32() * 1000.0);
+            }
+            last_sample = Some(time);
+
+            let mut sent = Ok(());
+            for (channel, (last_on, (on, _))) in last_on.iter_mut().zip(&readings).enumerate() {
+                if *last_on != Some(*on) {
+                    sent = sent.and(sender.send(CaptureMessage::Transition {
+                        channel,
+                        time,
+                        on: *on,
+                    }));
+                    *last_on = Some(*on);
+                }
+            }
+            if time.saturating_sub(last_status) >= Self::STATUS_INTERVAL && !intervals.is_empty() {
+                let mean = intervals.iter().sum::<f32>() / intervals.len() as f32;
+                let variance = intervals.iter().map(|x| (x - mean).powi(2)).sum::<f32>()
+                    / intervals.len() as f32;
+                sent = sent.and(sender.send(CaptureMessage::Status {
+                    time,
+                    readings,
+                    sample_rate: 1000.0 / mean,
+                    jitter: variance.sqrt(),
+                }));
+                intervals.clear();
+                last_status = time;
+            }
+            if sent.is_err() {
+                // The receiver was dropped.
+                return;
+            }
+
+            if live {
+                // Wait for the next sample, without trying to catch up if behind.
+                let period = Duration::from_secs_f32(1.0 / settings.sample_rate.max(1.0));
+                deadline += period;
+                let now = Instant::now();
+                if deadline > now {
+                    Self::sleep_until(deadline, &stop);
+                } else {
+                    deadline = now;
+                }
+            } else {
+                // Play back at the recorded speed.
+                Self::sleep_until(start + recorded, &stop);
+            }
+        }
+    }
+
+    /// Sleep until `deadline`, waking up every [`Capture::STATUS_INTERVAL`] to return early
+    /// once `stop` is set, so stopping never waits for a long gap of a recording.
+    fn sleep_until(deadline: Instant, stop: &AtomicBool) {
+        loop {
+            let now = Instant::now();
+            if deadline <= now || stop.load(Ordering::Relaxed) {
+                return;
+            }
+            thread::sleep((deadline - now).min(Self::STATUS_INTERVAL));
+        }
+    }
+}
+
+impl Drop for Capture {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
@@ -0,0 +1,627 @@
+use std::time::{Duration, Instant};
+
+use egui::{
+    plot::{Bar, BarChart, Plot, PlotPoint, Text, VLine},
+    text::LayoutJob,
+    DragValue, TextFormat,
+};
+use morse::{
+    alphabet::AlphabetSettings,
+    audio::ToneSettings,
+    dictionary::Dictionary,
+    morse_decoder::{Code, DecoderSettings, MorseDecoder},
+    morse_encoder::{encode, EncoderSettings, Schedule},
+    sensor::{Calibration, Reading, SensorSettings},
+    signal::SourceChannel,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::sensor_indicator;
+
+/// A named sensor with its own decoder settings.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct ChannelSettings {
+    pub name: String,
+    pub sensor: SensorSettings,
+    pub decoder: DecoderSettings,
+    /// Tone of this channel when decoding audio.
+    #[serde(default)]
+    pub tone: ToneSettings,
+}
+
+impl ChannelSettings {
+    pub fn new(name: String) -> Self {
+        Self {
+            name,
+            sensor: SensorSettings::default(),
+            decoder: DecoderSettings::default(),
+            tone: ToneSettings::default(),
+        }
+    }
+
+    /// What the capture thread samples for this channel.
+    pub fn source(&self) -> SourceChannel {
+        SourceChannel {
+            sensor: self.sensor,
+            tone: self.tone,
+        }
+    }
+}
+
+/// A sensor being decoded independently of the others.
+pub struct Channel {
+    pub settings: ChannelSettings,
+    pub decoder: MorseDecoder,
+    /// Latest reading from the capture thread and whether it was classified as on.
+    pub reading: Option<(bool, Reading)>,
+    /// Start time of the running calibration.
+    calibrating: Option<Instant>,
+    /// Colors collected while calibrating.
+    calibration_colors: Vec<(u8, u8, u8)>,
+    /// Result of the last calibration, or [`None`] if it failed.
+    calibration: Option<Option<Calibration>>,
+    /// Why the selected alphabet or word list could not be loaded, leaving the previous
+    /// one in use.
+    load_error: Option<String>,
+    /// Threshold line being dragged in the timing histograms, with the width of its
+    /// histogram (ms), which is kept until the line is released.
+    dragging: Option<(Threshold, f64)>,
+}
+
+/// A duration threshold of [`DecoderSettings`] drawn on the timing histograms.
+#[derive(Clone, Copy, PartialEq)]
+enum Threshold {
+    DitDah,
+    LongDah,
+    ExtraLongDah,
+    Space,
+    Letter,
+    Word,
+}
+
+impl Threshold {
+    /// Thresholds of the mark durations with the alphabet of `settings`.
+    fn marks(settings: &DecoderSettings) -> &'static [Self] {
+        if settings.american() {
+            &[Self::DitDah, Self::LongDah, Self::ExtraLongDah]
+        } else {
+            &[Self::DitDah]
+        }
+    }
+
+    /// Thresholds of the gap durations with the alphabet of `settings`.
+    fn gaps(settings: &DecoderSettings) -> &'static [Self] {
+        if settings.american() {
+            &[Self::Space, Self::Letter, Self::Word]
+        } else {
+            &[Self::Letter, Self::Word]
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Self::DitDah => "Dah",
+            Self::LongDah => "Long Dash",
+            Self::ExtraLongDah => "Longer Dash",
+            Self::Space => "Space",
+            Self::Letter => "Letter",
+            Self::Word => "Word",
+        }
+    }
+
+    fn value(self, settings: &mut DecoderSettings) -> &mut u64 {
+        match self {
+            Self::DitDah => &mut settings.dit_dah,
+            Self::LongDah => &mut settings.american.long_dah,
+            Self::ExtraLongDah => &mut settings.american.extra_long_dah,
+            Self::Space => &mut settings.american.space,
+            Self::Letter => &mut settings.letter,
+            Self::Word => &mut settings.letter_word,
+        }
+    }
+}
+
+impl Channel {
+    pub fn new(settings: ChannelSettings) -> Self {
+        let mut channel = Self {
+            settings,
+            decoder: MorseDecoder::new(),
+            reading: None,
+            calibrating: None,
+            calibration_colors: Vec::new(),
+            calibration: None,
+            load_error: None,
+            dragging: None,
+        };
+        channel.load();
+        channel
+    }
+
+    /// Load the alphabet and the word list selected in the decoder settings into the decoder.
+    fn load(&mut self) {
+        let settings = &self.settings.decoder;
+        let result = settings.alphabet.load().and_then(|alphabet| {
+            let dictionary = if settings.correction {
+                Some(Dictionary::load(&settings.word_list)?)
+            } else {
+                None
+            };
+            Ok((alphabet, dictionary))
+        });
+        self.load_error = match result {
+            Ok((alphabet, dictionary)) => {
+                self.decoder.set_alphabet(alphabet);
+                self.decoder.set_dictionary(dictionary);
+                None
+            }
+            Err(error) => {
+                eprintln!("[Channel] {}: {error}", self.settings.name);
+                Some(error)
+            }
+        };
+    }
+
+    /// Why the selected alphabet or word list could not be loaded, if it failed.
+    pub fn load_error(&self) -> Option<&str> {
+        self.load_error.as_deref()
+    }
+
+    /// Encode `text` with the alphabet of the decoder and time it, with the American
+    /// Morse timing if it is selected.
+    pub fn schedule(&self, text: &str, settings: &EncoderSettings) -> Schedule {
+        let code = encode(text, self.decoder.alphabet());
+        if self.settings.decoder.american() {
+            Schedule::american(&code, settings)
+        } else {
+            Schedule::new(&code, settings)
+        }
+    }
+
+    /// How long colors are collected for calibration.
+    const CALIBRATION_TIME: Duration = Duration::from_secs(5);
+
+    /// Store the latest reading from the capture thread.
+    pub fn update_reading(&mut self, on: bool, reading: Reading) {
+        self.reading = Some((on, reading));
+        if let Some(start) = self.calibrating {
+            self.calibration_colors.push(reading.color);
+            if start.elapsed() >= Self::CALIBRATION_TIME {
+                let calibration = self.settings.sensor.calibrate(&self.calibration_colors);
+                if let Some(calibration) = &calibration {
+                    self.settings.sensor.apply_calibration(calibration);
+                }
+                self.calibration = Some(calibration);
+                self.calibrating = None;
+                self.calibration_colors.clear();
+            }
+        }
+    }
+
+    /// Forget the latest reading and cancel calibration when capturing stops.
+    pub fn clear_reading(&mut self) {
+        self.reading = None;
+        self.calibrating = None;
+        self.calibration_colors.clear();
+    }
+
+    /// Show the channel's signal, decoded text and decoder settings.
+    /// `id` distinguishes the widgets of each channel.
+    pub fn ui(&mut self, ui: &mut egui::Ui, id: usize) {
+        let sensor = &mut self.settings.sensor;
+        let decoder_settings = &mut self.settings.decoder;
+        let tone = &mut self.settings.tone;
+
+        // Sensor
+        if let Some((on, reading)) = self.reading {
+            sensor_indicator(ui, sensor, reading, on);
+        }
+
+        // Calibration
+        ui.horizontal(|ui| {
+            match &self.calibrating {
+                Some(start) => {
+                    ui.add_enabled(false, egui::Button::new("Calibrate"));
+                    ui.label(format!(
+                        "Calibrating, keep the signal flashing... {:.0} s",
+                        Self::CALIBRATION_TIME
+                            .saturating_sub(start.elapsed())
+                            .as_secs_f32()
+                            .ceil()
+                    ));
+                }
+                None => {
+                    if ui
+                        .add_enabled(self.reading.is_some(), egui::Button::new("Calibrate"))
+                        .on_hover_text("Learn the on and off colors and the threshold from the signal while recording.")
+                        .clicked()
+                    {
+                        self.calibrating = Some(Instant::now());
+                    }
+                    match &self.calibration {
+                        Some(Some(calibration)) => ui.label(format!(
+                            "Separation: {:.2} ({}), {:.0}% on",
+                            calibration.separation,
+                            calibration.quality(),
+                            calibration.on_fraction * 100.0
+                        )),
+                        Some(None) => ui.label("Calibration failed: the signal did not change."),
+                        None => ui.label(""),
+                    };
+                }
+            }
+        });
+
+        let estimate = self.decoder.estimate_timing();
+
+        // Display speed
+        ui.label(match self.decoder.speed() {
+            Some(speed) => format!("Speed: {speed}"),
+            None => "Speed: Unknown".to_owned(),
+        });
+
+        // Display recent code and the transcript
+        ui.horizontal_top(|ui| {
+            ui.label(Code::display_code_string(
+                &self.decoder.decode(decoder_settings),
+            ));
+            ui.separator();
+            egui::ScrollArea::vertical()
+                .id_source(("transcript", id))
+                .max_height(200.0)
+                .stick_to_bottom(true)
+                .show(ui, |ui| {
+                    let transcript =
+                        shade_transcript(ui, self.decoder.transcript(), self.decoder.confidence());
+                    ui.add(egui::Label::new(transcript).wrap(true));
+                    if decoder_settings.correction {
+                        ui.separator();
+                        let changes: Vec<String> = self
+                            .decoder
+                            .corrections()
+                            .iter()
+                            .filter(|(word, correction)| word != correction)
+                            .map(|(word, correction)| format!("{word} → {correction}"))
+                            .collect();
+                        let corrected = highlight_corrections(ui, &self.decoder);
+                        ui.add(egui::Label::new(corrected).wrap(true))
+                            .on_hover_text(if changes.is_empty() {
+                                "No corrections".to_owned()
+                            } else {
+                                changes.join("\n")
+                            });
+                    }
+                });
+        });
+
+        // Decoder settings
+        ui.add(egui::Slider::new(&mut sensor.on_threshold, 0.0..=1.0).text("On Threshold"));
+        let mut off_threshold = sensor.off_threshold();
+        if ui
+            .add(egui::Slider::new(&mut off_threshold, 0.0..=1.0).text("Off Threshold"))
+            .changed()
+        {
+            sensor.off_threshold = Some(off_threshold);
+        }
+        let mut load = false;
+        egui::Grid::new(("decoder settings", id)).show(ui, |ui| {
+            ui.label("Dit/Dah Threshold (ms)");
+            ui.add(DragValue::new(&mut decoder_settings.dit_dah));
+            ui.end_row();
+            ui.label("Minimum Letter Gap (ms)");
+            ui.add(DragValue::new(&mut decoder_settings.letter));
+            ui.end_row();
+            ui.label("Minimum Word Gap (ms)");
+            ui.add(DragValue::new(&mut decoder_settings.letter_word));
+            ui.end_row();
+            if decoder_settings.american() {
+                let american = &mut decoder_settings.american;
+                ui.label("Long Dash Threshold (ms)");
+                ui.add(DragValue::new(&mut american.long_dah))
+                    .on_hover_text("Longer marks are the long dash of L.");
+                ui.end_row();
+                ui.label("Longer Dash Threshold (ms)");
+                ui.add(DragValue::new(&mut american.extra_long_dah))
+                    .on_hover_text("Longer marks are the longer dash of 0.");
+                ui.end_row();
+                ui.label("Minimum Space (ms)");
+                ui.add(DragValue::new(&mut american.space))
+                    .on_hover_text("Gaps from this to the letter gap are spaces within a character, as in C (.. .).");
+                ui.end_row();
+            }
+            ui.label("Glitch Filter (ms)");
+            ui.add(DragValue::new(&mut decoder_settings.glitch));
+            ui.end_row();
+            ui.label("Adaptive Timing");
+            ui.checkbox(&mut decoder_settings.adaptive, "");
+            ui.end_row();
+            ui.label("Probabilistic Decoding");
+            ui.checkbox(&mut decoder_settings.probabilistic, "")
+                .on_hover_text("Decode the most likely letters given the durations and fade letters with a low confidence.");
+            ui.end_row();
+            ui.label("Prefer Prosigns");
+            ui.checkbox(&mut decoder_settings.prosigns, "")
+                .on_hover_text("Show AR, BT, KN and AS as prosigns instead of + = ( and &.");
+            ui.end_row();
+            ui.label("Error Deletes Word");
+            ui.checkbox(&mut decoder_settings.error_deletes_word, "")
+                .on_hover_text(
+                    "Delete the previous word when the error prosign (8 dits) is received.",
+                );
+            ui.end_row();
+            ui.label("Alphabet");
+            ui.horizontal(|ui| {
+                let alphabet = &mut decoder_settings.alphabet;
+                let custom = matches!(alphabet, AlphabetSettings::Custom { .. });
+                egui::ComboBox::from_id_source(("alphabet", id))
+                    .selected_text(if custom { "Custom" } else { alphabet.name() })
+                    .show_ui(ui, |ui| {
+                        for (option, name) in AlphabetSettings::BUILT_IN {
+                            load |= ui.selectable_value(alphabet, option, name).changed();
+                        }
+                        if ui.selectable_label(custom, "Custom").clicked() && !custom {
+                            *alphabet = AlphabetSettings::Custom {
+                                path: "alphabet.toml".to_owned(),
+                            };
+                        }
+                    });
+                if let AlphabetSettings::Custom { path } = alphabet {
+                    ui.add(egui::TextEdit::singleline(path).desired_width(120.0))
+                        .on_hover_text("TOML file with a [characters] table of patterns, such as \".-\" = \"A\".");
+                    load |= ui.button("Load").clicked();
+                }
+            });
+            ui.end_row();
+            ui.label("Word Correction");
+            ui.horizontal(|ui| {
+                load |= ui
+                    .checkbox(&mut decoder_settings.correction, "")
+                    .on_hover_text("Correct unknown words to the closest word of the word list and ham abbreviations.")
+                    .changed();
+                if decoder_settings.correction {
+                    ui.add(
+                        egui::TextEdit::singleline(&mut decoder_settings.word_list)
+                            .desired_width(120.0)
+                            .hint_text("Built-in words"),
+                    )
+                    .on_hover_text("File with additional words, one per line.");
+                    load |= ui.button("Load").clicked();
+                }
+            });
+            ui.end_row();
+            if let Some(error) = &self.load_error {
+                ui.label("");
+                ui.colored_label(egui::Color32::RED, error);
+                ui.end_row();
+            }
+            ui.label("Audio Tone (Hz)");
+            ui.add(DragValue::new(&mut tone.frequency).clamp_range(1.0..=20000.0));
+            ui.end_row();
+            ui.label("Audio Block (ms)");
+            ui.add(DragValue::new(&mut tone.block).clamp_range(1.0..=100.0));
+        });
+        if load {
+            self.load();
+        }
+        match estimate {
+            Some(estimate) => ui.label(format!(
+                "Estimated (ms): dit {:.0}, dah {:.0}, element gap {:.0}, letter gap {:.0}, word gap {:.0}",
+                estimate.dit,
+                estimate.dah,
+                estimate.element_gap,
+                estimate.letter_gap,
+                estimate.word_gap
+            )),
+            None => ui.label("Estimated (ms): waiting for dits and dahs"),
+        };
+
+        // Display recorded timings
+        let decoder_settings = &mut self.settings.decoder;
+        ui.columns(2, |columns| {
+            for (ui, on) in columns.iter_mut().zip([false, true]) {
+                let thresholds = if on {
+                    Threshold::gaps(decoder_settings)
+                } else {
+                    Threshold::marks(decoder_settings)
+                };
+                ui.label(if on { "Gaps (ms)" } else { "Marks (ms)" })
+                    .on_hover_text(
+                        "Drag the lines to set the thresholds, which turns off adaptive timing.",
+                    );
+                timing_histogram(
+                    ui,
+                    (on, id),
+                    &self.decoder.durations(on),
+                    thresholds,
+                    decoder_settings,
+                    &mut self.dragging,
+                );
+            }
+        });
+        egui::CollapsingHeader::new("Durations")
+            .id_source(("durations", id))
+            .show(ui, |ui| {
+                egui::ScrollArea::vertical()
+                    .max_height(200.0)
+                    .show(ui, |ui| {
+                        ui.label(self.decoder.display());
+                    });
+            });
+    }
+}
+
+/// Plot a histogram of `durations` (ms) with `thresholds` as vertical lines that can be
+/// dragged to change them in `settings`. Dragging a line turns off adaptive timing, which
+/// would otherwise move it back. The histogram spans twice the longest threshold,
+/// and longer durations, such as pauses between transmissions, are only counted.
+fn timing_histogram(
+    ui: &mut egui::Ui,
+    id: impl std::hash::Hash,
+    durations: &[u64],
+    thresholds: &[Threshold],
+    settings: &mut DecoderSettings,
+    dragging: &mut Option<(Threshold, f64)>,
+) {
+    const BINS: usize = 40;
+    /// Distance (points) from a line at which it can be grabbed.
+    const GRAB: f32 = 6.0;
+
+    // Keep the scale while dragging, so the line stays under the pointer
+    let width = match dragging {
+        Some((threshold, width)) if thresholds.contains(threshold) => *width,
+        _ => {
+            let longest = thresholds
+                .iter()
+                .map(|threshold| *threshold.value(settings))
+                .max()
+                .unwrap_or(0);
+            (longest as f64 * 2.0).max(BINS as f64)
+        }
+    };
+    let bin = width / BINS as f64;
+    let mut counts = [0u32; BINS];
+    let mut longer = 0;
+    for duration in durations {
+        match counts.get_mut((*duration as f64 / bin) as usize) {
+            Some(count) => *count += 1,
+            None => longer += 1,
+        }
+    }
+    let highest = counts.iter().copied().max().unwrap_or(0).max(1) as f64;
+
+    let values: Vec<(Threshold, f64)> = thresholds
+        .iter()
+        .map(|threshold| (*threshold, *threshold.value(settings) as f64))
+        .collect();
+    let active = dragging.map(|(threshold, _)| threshold);
+    let color = ui.visuals().warn_fg_color;
+    let response = Plot::new(id)
+        .height(120.0)
+        .allow_drag(false)
+        .allow_zoom(false)
+        .allow_scroll(false)
+        .allow_boxed_zoom(false)
+        .allow_double_click_reset(false)
+        .show_y(false)
+        .include_x(0.0)
+        .include_x(width)
+        .include_y(0.0)
+        .include_y(highest * 1.1)
+        .show(ui, |plot_ui| {
+            let bars = counts
+                .iter()
+                .enumerate()
+                .map(|(i, count)| Bar::new((i as f64 + 0.5) * bin, *count as f64).width(bin))
+                .collect();
+            plot_ui.bar_chart(BarChart::new(bars));
+            for (threshold, value) in &values {
+                let highlight = active == Some(*threshold);
+                plot_ui.vline(VLine::new(*value).color(color).width(if highlight {
+                    3.0
+                } else {
+                    1.5
+                }));
+                plot_ui.text(
+                    Text::new(PlotPoint::new(*value, highest * 1.1), threshold.name())
+                        .color(color)
+                        .anchor(egui::Align2::LEFT_TOP),
+                );
+            }
+
+            // Screen positions of the pointer and the lines
+            let pointer = plot_ui
+                .ctx()
+                .input(|i| i.pointer.interact_pos())
+                .map(|position| (position.x, plot_ui.plot_from_screen(position).x));
+            let lines: Vec<f32> = values
+                .iter()
+                .map(|(_, value)| plot_ui.screen_from_plot(PlotPoint::new(*value, 0.0)).x)
+                .collect();
+            (pointer, lines)
+        });
+    let (pointer, lines) = response.inner;
+    let response = response.response;
+
+    // The line nearest to the pointer, if it is close enough to grab
+    let nearest = pointer.and_then(|(x, _)| {
+        lines
+            .iter()
+            .zip(thresholds)
+            .map(|(line, threshold)| ((line - x).abs(), *threshold))
+            .filter(|(distance, _)| *distance <= GRAB)
+            .min_by(|a, b| a.0.total_cmp(&b.0))
+            .map(|(_, threshold)| threshold)
+    });
+    if response.drag_started() {
+        if let Some(threshold) = nearest {
+            settings.adaptive = false;
+            *dragging = Some((threshold, width));
+        }
+    }
+    match dragging {
+        Some((threshold, _)) if thresholds.contains(threshold) => {
+            if let Some((_, value)) = pointer {
+                *threshold.value(settings) = value.clamp(0.0, width).round() as u64;
+            }
+            if !response.dragged() {
+                *dragging = None;
+            }
+            ui.ctx().set_cursor_icon(egui::CursorIcon::ResizeHorizontal);
+        }
+        _ => {
+            if nearest.is_some() && response.hovered() {
+                ui.ctx().set_cursor_icon(egui::CursorIcon::ResizeHorizontal);
+            }
+        }
+    }
+    if longer > 0 {
+        ui.weak(format!("{longer} longer than {width:.0} ms"));
+    }
+}
+
+/// Lay out the transcript with each character faded by how low its confidence is.
+fn shade_transcript(ui: &egui::Ui, transcript: &str, confidence: &[f32]) -> LayoutJob {
+    let color = ui.visuals().text_color();
+    let font = egui::TextStyle::Body.resolve(ui.style());
+    let mut job = LayoutJob::default();
+    let mut append = |text: &str, shade: f32| {
+        let format = TextFormat::simple(font.clone(), color.gamma_multiply(0.2 + 0.8 * shade));
+        job.append(text, 0.0, format);
+    };
+
+    // Characters with the same shade, in steps of 0.1, are appended as one section
+    let mut section: Option<(usize, f32)> = None;
+    for ((index, _), confidence) in transcript.char_indices().zip(confidence) {
+        let shade = (confidence * 10.0).round() / 10.0;
+        match section {
+            Some((_, previous)) if previous == shade => {}
+            Some((start, previous)) => {
+                append(&transcript[start..index], previous);
+                section = Some((index, shade));
+            }
+            None => section = Some((index, shade)),
+        }
+    }
+    if let Some((start, shade)) = section {
+        append(&transcript[start..], shade);
+    }
+    job
+}
+
+/// Lay out the corrected transcript with the corrected words highlighted.
+fn highlight_corrections(ui: &egui::Ui, decoder: &MorseDecoder) -> LayoutJob {
+    let font = egui::TextStyle::Body.resolve(ui.style());
+    let text = TextFormat::simple(font.clone(), ui.visuals().text_color());
+    let highlight = TextFormat::simple(font, ui.visuals().warn_fg_color);
+    let mut job = LayoutJob::default();
+    for (word, correction) in decoder.corrections() {
+        let format = if word == correction {
+            &text
+        } else {
+            &highlight
+        };
+        job.append(correction, 0.0, format.clone());
+        job.append(" ", 0.0, text.clone());
+    }
+    job.append(decoder.uncorrected().trim_start(), 0.0, text);
+    job
+}
@@ -0,0 +1,501 @@
+use std::{
+    io::Write,
+    time::{Duration, Instant},
+};
+
+use screenshots::Screen;
+
+use morse::{
+    alphabet::AlphabetSettings,
+    audio::{synthesize, write_wav},
+    morse_decoder::{MorseDecoder, Speed},
+    morse_encoder::{self, Schedule},
+    sensor::{Aggregate, ColorMode},
+    signal::{SignalSource, SourceChannel},
+};
+
+use crate::{
+    capture::{Capture, CaptureMessage, CaptureSettings},
+    channel::Channel,
+    load_config,
+    source::SourceSettings,
+};
+
+const USAGE: &str = "Usage: morse decode [options]
+
+Samples the sensors configured in config.toml and prints the decoded text to stdout.
+With several channels, each update is printed on its own line prefixed by the channel name.
+With --wav, the tone of each channel is decoded from a WAV file instead of the screen.
+With --synthetic, a text is generated at a fixed speed for testing.
+With --frames, the sensors are read from a directory of numbered PNG frames, such as
+frame_0001.png, with positions in frame pixels.
+
+Options:
+  --channel <name>         Decode only this channel. The sensor and timing options apply to
+                           this channel, or to the first channel if omitted.
+  --screen <id>            Display id of the screen to sample.
+  --position <x,y>         Top left corner of the sensor region on the screen.
+  --size <w,h>             Size of the sensor region.
+  --aggregate <mode>       Combine the region's pixels by mean, median or fraction.
+  --color-mode <mode>      Compare colors by average, luminance, lab, projection, red, green,
+                           blue or hue.
+  --on-color <r,g,b>       Sensor color while the signal is on.
+  --off-color <r,g,b>      Sensor color while the signal is off.
+  --threshold <0-1>        On threshold, the signal switches on at or above it.
+  --off-threshold <0-1>    Off threshold, the signal switches off below it.
+  --pixel-threshold <0-1>  Value of a pixel to count as on with --aggregate fraction.
+  --dit-dah <ms>           Dit/dah threshold.
+  --letter <ms>            Minimum letter gap.
+  --word <ms>              Minimum word gap.
+  --glitch <ms>            Ignore marks and gaps shorter than this.
+  --adaptive               Retune the timing thresholds from the received signal.
+  --probabilistic          Decode the most likely letters given the durations.
+  --correct                Correct unknown words of recordings with a word list.
+  --word-list <file>       Additional words for --correct, one per line.
+  --prosigns               Show AR, BT, KN and AS as prosigns instead of + = ( and &.
+  --error-deletes-word     Delete the previous word on the error prosign (8 dits).
+  --alphabet <name|file>   Translate with latin, russian, greek, wabun, hebrew, arabic,
+                           korean or american, or a custom alphabet from a TOML file.
+  --long-dash <ms>         American Morse long dash (L) threshold.
+  --longer-dash <ms>       American Morse longer dash (0) threshold.
+  --space <ms>             Minimum American Morse space within a character.
+  --sample-rate <Hz>       Target samples per second.
+  --wav <file>             Decode a WAV recording instead of the screen.
+  --tone <Hz>              Frequency of the tone to detect in the recording.
+  --block <ms>             Length of the blocks the recording is analysed in.
+  --frames <directory>     Replay a directory of numbered PNG frames instead of the screen.
+  --fps <n>                Frame rate of the frames, 30 if omitted.
+  --timestamps <file>      File with the time of each frame in seconds, one per line.
+  --synthetic <text>       Decode the text generated as Morse code instead of the screen.
+  --wpm <n>                Speed of the generated text, 20 if omitted.
+  --help                   Show this message.";
+
+const ENCODE_USAGE: &str = "Usage: morse encode [options]
+
+Generates Morse code audio from text and writes it to a WAV file. Defaults are taken
+from the transmitter settings in config.toml.
+
+Options:
+  --text <text>            Text to encode.
+  --input <file>           Encode the text of a file.
+  --output <file>          WAV file to write, morse.wav if omitted.
+  --wpm <n>                Character speed in words per minute.
+  --farnsworth <n>         Effective speed with stretched letter and word gaps.
+  --alphabet <name|file>   Encode with latin, russian, greek, wabun, hebrew, arabic, korean
+                           or american, or a custom alphabet from a TOML file.
+  --tone <Hz>              Frequency of the tone.
+  --sample-rate <Hz>       Samples per second of the audio.
+  --amplitude <0-1>        Peak amplitude of the tone.
+  --rise <ms>              Rise and fall time of each mark, avoiding key clicks.
+  --noise <0-1>            Standard deviation of added white noise.
+  --help                   Show this message.";
+
+/// Parse a comma separated list of exactly `N` values.
+fn parse_list<T: std::str::FromStr, const N: usize>(
+    flag: &str,
+    value: &str,
+) -> Result<[T; N], String> {
+    let values: Vec<T> = value
+        .split(',')
+        .map(|x| x.trim().parse())
+        .collect::<Result<_, _>>()
+        .map_err(|_| format!("Invalid value \"{value}\" for {flag}."))?;
+    values
+        .try_into()
+        .map_err(|_| format!("Expected {N} comma separated values for {flag}."))
+}
+
+fn parse<T: std::str::FromStr>(flag: &str, value: &str) -> Result<T, String> {
+    value
+        .parse()
+        .map_err(|_| format!("Invalid value \"{value}\" for {flag}."))
+}
+
+/// A built-in alphabet by its lowercase name, or else a custom alphabet file.
+fn parse_alphabet(value: &str) -> AlphabetSettings {
+    match value {
+        "latin" => AlphabetSettings::Latin,
+        "russian" => AlphabetSettings::Russian,
+        "greek" => AlphabetSettings::Greek,
+        "wabun" => AlphabetSettings::Wabun,
+        "hebrew" => AlphabetSettings::Hebrew,
+        "arabic" => AlphabetSettings::Arabic,
+        "korean" => AlphabetSettings::Korean,
+        "american" => AlphabetSettings::American,
+        _ => AlphabetSettings::Custom {
+            path: value.to_owned(),
+        },
+    }
+}
+
+/// Run the headless decoder with the command line arguments following the `decode` subcommand.
+pub fn run(args: impl Iterator<Item = String>) -> Result<(), String> {
+    let mut config = load_config()?;
+    let args: Vec<String> = args.collect();
+    let args_contain_correct = args.iter().any(|flag| flag == "--correct");
+
+    // Select the channel the sensor and timing options apply to
+    if let Some(i) = args.iter().position(|flag| flag == "--channel") {
+        let name = args
+            .get(i + 1)
+            .ok_or_else(|| format!("Missing value for --channel.\n\n{USAGE}"))?;
+        config.channels.retain(|channel| &channel.name == name);
+        if config.channels.is_empty() {
+            return Err(format!("No channel named \"{name}\"."));
+        }
+    }
+    let selected = &mut config.channels[0];
+    let mut frames: Option<String> = None;
+    let mut fps = 30.0;
+    let mut timestamps = String::new();
+    let mut synthetic: Option<String> = None;
+    let mut wpm = 20.0;
+
+    let mut args = args.iter();
+    while let Some(flag) = args.next() {
+        if flag == "--help" {
+            println!("{USAGE}");
+            return Ok(());
+        }
+        let switch = match flag.as_str() {
+            "--adaptive" => Some(&mut selected.decoder.adaptive),
+            "--probabilistic" => Some(&mut selected.decoder.probabilistic),
+            "--correct" => Some(&mut selected.decoder.correction),
+            "--prosigns" => Some(&mut selected.decoder.prosigns),
+            "--error-deletes-word" => Some(&mut selected.decoder.error_deletes_word),
+            _ => None,
+        };
+        if let Some(switch) = switch {
+            *switch = true;
+            continue;
+        }
+        let value = args
+            .next()
+            .ok_or_else(|| format!("Missing value for {flag}.\n\n{USAGE}"))?;
+        let sensor = &mut selected.sensor;
+        let decoder = &mut selected.decoder;
+        match flag.as_str() {
+            "--channel" => {}
+            "--screen" => config.screen = Some(parse(flag, value)?),
+            "--position" => {
+                let [x, y] = parse_list(flag, value)?;
+                sensor.position = (x, y);
+            }
+            "--size" => {
+                let [width, height] = parse_list(flag, value)?;
+                sensor.size = (width, height);
+            }
+            "--aggregate" => {
+                sensor.aggregate = match value.as_str() {
+                    "mean" => Aggregate::Mean,
+                    "median" => Aggregate::Median,
+                    "fraction" => Aggregate::Fraction,
+                    _ => return Err(format!("Invalid value \"{value}\" for {flag}.")),
+                }
+            }
+            "--color-mode" => {
+                sensor.mode = match value.as_str() {
+                    "average" => ColorMode::Average,
+                    "luminance" => ColorMode::Luminance,
+                    "lab" => ColorMode::Lab,
+                    "projection" => ColorMode::Projection,
+                    "red" => ColorMode::Red,
+                    "green" => ColorMode::Green,
+                    "blue" => ColorMode::Blue,
+                    "hue" => ColorMode::Hue,
+                    _ => return Err(format!("Invalid value \"{value}\" for {flag}.")),
+                }
+            }
+            "--on-color" => {
+                let [r, g, b] = parse_list(flag, value)?;
+                sensor.on_color = (r, g, b);
+            }
+            "--off-color" => {
+                let [r, g, b] = parse_list(flag, value)?;
+                sensor.off_color = (r, g, b);
+            }
+            "--threshold" => sensor.on_threshold = parse(flag, value)?,
+            "--off-threshold" => sensor.off_threshold = Some(parse(flag, value)?),
+            "--pixel-threshold" => sensor.pixel_threshold = parse(flag, value)?,
+            "--glitch" => decoder.glitch = parse(flag, value)?,
+            "--dit-dah" => decoder.dit_dah = parse(flag, value)?,
+            "--letter" => decoder.letter = parse(flag, value)?,
+            "--word" => decoder.letter_word = parse(flag, value)?,
+            "--word-list" => decoder.word_list = value.clone(),
+            "--long-dash" => decoder.american.long_dah = parse(flag, value)?,
+            "--longer-dash" => decoder.american.extra_long_dah = parse(flag, value)?,
+            "--space" => decoder.american.space = parse(flag, value)?,
+            "--alphabet" => decoder.alphabet = parse_alphabet(value),
+            "--sample-rate" => config.sample_rate = parse(flag, value)?,
+            "--wav" => {
+                config.source = SourceSettings::Audio {
+                    path: value.clone(),
+                }
+            }
+            "--tone" => selected.tone.frequency = parse(flag, value)?,
+            "--block" => selected.tone.block = parse(flag, value)?,
+            "--frames" => frames = Some(value.clone()),
+            "--fps" => fps = parse(flag, value)?,
+            "--timestamps" => timestamps = value.clone(),
+            "--synthetic" => synthetic = Some(value.clone()),
+            "--wpm" => wpm = parse(flag, value)?,
+            _ => return Err(format!("Unknown option {flag}.\n\n{USAGE}")),
+        }
+    }
+
+    if let Some(directory) = frames {
+        config.source = SourceSettings::Frames {
+            directory,
+            fps,
+            timestamps,
+        };
+    }
+    if let Some(text) = synthetic {
+        config.source = SourceSettings::Synthetic { text, wpm };
+    }
+    let mut channels: Vec<Channel> = config.channels.into_iter().map(Channel::new).collect();
+    if let Some(error) = channels.iter().find_map(Channel::load_error) {
+        return Err(error.to_owned());
+    }
+
+    // Find the screen
+    let screen = if config.source == SourceSettings::Screen {
+        let screens = Screen::all().map_err(|error| format!("Failed to find screens: {error}."))?;
+        let screen = match config.screen {
+            Some(id) => *screens
+                .iter()
+                .find(|screen| screen.display_info.id == id)
+                .ok_or_else(|| format!("No screen with id {id}."))?,
+            None => *screens.first().ok_or("No screens found.")?,
+        };
+        for channel in channels.iter().map(|channel| &channel.settings) {
+            eprintln!(
+                "[Decoder] Sampling \"{}\" on screen {} at ({}, {}), {}x{}.",
+                channel.name,
+                screen.display_info.id,
+                channel.sensor.position.0,
+                channel.sensor.position.1,
+                channel.sensor.size.0,
+                channel.sensor.size.1
+            );
+        }
+        Some(screen)
+    } else {
+        None
+    };
+
+    // Recordings are decoded as fast as they can be read
+    let epoch = Instant::now();
+    let mut source = config.source.open(screen, epoch)?;
+    if !source.live() {
+        return decode_recorded(source.as_mut(), &mut channels);
+    }
+    if args_contain_correct {
+        return Err("--correct only applies to recordings (--wav and --frames).".to_owned());
+    }
+
+    // Sample and print newly decoded text
+    let capture = Capture::start(
+        source,
+        CaptureSettings {
+            channels: channels
+                .iter()
+                .map(|channel| channel.settings.source())
+                .collect(),
+            sample_rate: config.sample_rate,
+        },
+        epoch,
+    );
+    let mut printed = vec![String::new(); channels.len()];
+    let mut stdout = std::io::stdout();
+    for message in capture.receiver.iter() {
+        match message {
+            CaptureMessage::Transition { channel, time, on } => {
+                let channel = &mut channels[channel];
+                channel.decoder.sample(on, time, &channel.settings.decoder);
+            }
+            CaptureMessage::Status { time, readings, .. } => {
+                for (channel, (on, _)) in channels.iter_mut().zip(readings) {
+                    channel.decoder.sample(on, time, &channel.settings.decoder);
+                }
+            }
+            CaptureMessage::End { .. } => return Ok(()),
+            CaptureMessage::Error(error) => return Err(error),
+        }
+
+        let single = channels.len() == 1;
+        for (channel, printed) in channels.iter_mut().zip(&mut printed) {
+            if channel.settings.decoder.adaptive {
+                channel.decoder.adapt(&mut channel.settings.decoder);
+            }
+
+            // A single channel is printed as a stream, multiple channels one line per update.
+            let transcript = channel.decoder.transcript();
+            if transcript == printed {
+                continue;
+            }
+            let common = transcript
+                .char_indices()
+                .zip(printed.chars())
+                .find(|((_, a), b)| a != b)
+                .map_or(transcript.len().min(printed.len()), |((i, _), _)| i);
+            if common < printed.len() && single {
+                // The error prosign deleted a word that was already printed, so continue
+                // the stream on a new line.
+                println!();
+            }
+            let new = &transcript[common..];
+            if !new.is_empty() {
+                if single {
+                    print!("{new}");
+                } else {
+                    println!("{}: {new}", channel.settings.name);
+                }
+                stdout
+                    .flush()
+                    .map_err(|error| format!("Unable to write to stdout: {error}."))?;
+            }
+            *printed = transcript.to_owned();
+        }
+    }
+    Ok(())
+}
+
+/// Run the audio generator with the command line arguments following the `encode` subcommand.
+pub fn encode(args: impl Iterator<Item = String>) -> Result<(), String> {
+    let mut settings = load_config()?.transmitter;
+    let mut farnsworth: Option<f32> = None;
+    let mut text: Option<String> = None;
+    let mut output = "morse.wav".to_owned();
+    let mut alphabet = AlphabetSettings::Latin;
+
+    let args: Vec<String> = args.collect();
+    let args_contain_wpm = args.iter().any(|flag| flag == "--wpm");
+    let mut args = args.iter();
+    while let Some(flag) = args.next() {
+        if flag == "--help" {
+            println!("{ENCODE_USAGE}");
+            return Ok(());
+        }
+        let value = args
+            .next()
+            .ok_or_else(|| format!("Missing value for {flag}.\n\n{ENCODE_USAGE}"))?;
+        let audio = &mut settings.audio;
+        match flag.as_str() {
+            "--text" => text = Some(value.clone()),
+            "--input" => {
+                text = Some(
+                    std::fs::read_to_string(value)
+                        .map_err(|error| format!("Unable to read {value}: {error}."))?,
+                )
+            }
+            "--output" => output = value.clone(),
+            "--wpm" => settings.encoder.wpm = parse(flag, value)?,
+            "--farnsworth" => farnsworth = Some(parse(flag, value)?),
+            "--alphabet" => alphabet = parse_alphabet(value),
+            "--tone" => audio.frequency = parse(flag, value)?,
+            "--sample-rate" => audio.sample_rate = parse(flag, value)?,
+            "--amplitude" => audio.amplitude = parse(flag, value)?,
+            "--rise" => audio.rise = parse(flag, value)?,
+            "--noise" => audio.noise = parse(flag, value)?,
+            _ => return Err(format!("Unknown option {flag}.\n\n{ENCODE_USAGE}")),
+        }
+    }
+    // Without --farnsworth, a new speed applies to the spacing too.
+    if let Some(farnsworth) = farnsworth {
+        settings.encoder.farnsworth_wpm = farnsworth;
+    } else if args_contain_wpm {
+        settings.encoder.farnsworth_wpm = settings.encoder.wpm;
+    }
+    let text = text.ok_or_else(|| format!("Missing --text or --input.\n\n{ENCODE_USAGE}"))?;
+
+    let code = morse_encoder::encode(&text, &alphabet.load()?);
+    let schedule = if alphabet == AlphabetSettings::American {
+        Schedule::american(&code, &settings.encoder)
+    } else {
+        Schedule::new(&code, &settings.encoder)
+    };
+    let samples = synthesize(&schedule, &settings.audio);
+    write_wav(output.as_ref(), &samples, settings.audio.sample_rate)?;
+    eprintln!(
+        "[Encoder] Wrote {:.1} s of audio at {} to {output}.",
+        schedule.duration().as_secs_f32(),
+        Speed {
+            wpm: settings.encoder.wpm,
+            farnsworth_wpm: settings.encoder.farnsworth_wpm.min(settings.encoder.wpm),
+        }
+    );
+    Ok(())
+}
+
+/// Read a recorded source to its end and print the transcript of every channel.
+fn decode_recorded(source: &mut dyn SignalSource, channels: &mut [Channel]) -> Result<(), String> {
+    let settings: Vec<SourceChannel> = channels
+        .iter()
+        .map(|channel| channel.settings.source())
+        .collect();
+    let mut signals: Vec<Vec<(Duration, bool)>> = vec![Vec::new(); channels.len()];
+    while let Some(sample) = source.next_sample(&settings)? {
+        for (signal, (on, _)) in signals.iter_mut().zip(sample.readings) {
+            signal.push((sample.time, on));
+        }
+    }
+    let single = channels.len() == 1;
+    for (channel, signal) in channels.iter_mut().zip(&signals) {
+        decode_recording(channel, signal, single);
+    }
+    Ok(())
+}
+
+/// Decode a complete recorded signal and print the channel's transcript.
+/// With adaptive timing, the durations of the whole signal are used to estimate the timing
+/// first instead of decoding the start with untuned thresholds.
+fn decode_recording(channel: &mut Channel, signal: &[(Duration, bool)], single: bool) {
+    let settings = &mut channel.settings;
+    if settings.decoder.adaptive {
+        let mut decoder = MorseDecoder::with_history(signal.len());
+        for (time, on) in signal {
+            decoder.sample(*on, *time, &settings.decoder);
+        }
+        decoder.adapt(&mut settings.decoder);
+    }
+
+    for (time, on) in signal {
+        channel.decoder.sample(*on, *time, &settings.decoder);
+        if settings.decoder.adaptive {
+            channel.decoder.adapt(&mut settings.decoder);
+        }
+    }
+    if let Some((time, _)) = signal.last() {
+        channel.decoder.finish(*time, &settings.decoder);
+    }
+
+    // With word correction, the corrected text is printed and the changes are logged.
+    let transcript = if settings.decoder.correction {
+        let changes: Vec<String> = channel
+            .decoder
+            .corrections()
+            .iter()
+            .filter(|(word, correction)| word != correction)
+            .map(|(word, correction)| format!("{word} -> {correction}"))
+            .collect();
+        if !changes.is_empty() {
+            eprintln!(
+                "[Decoder] \"{}\" corrected {}.",
+                settings.name,
+                changes.join(", ")
+            );
+        }
+        channel.decoder.corrected_transcript()
+    } else {
+        channel.decoder.transcript().to_owned()
+    };
+    if single {
+        println!("{transcript}");
+    } else {
+        println!("{}: {transcript}", settings.name);
+    }
+    if let Some(speed) = channel.decoder.speed() {
+        eprintln!("[Decoder] \"{}\" at {speed}.", settings.name);
+    }
+}
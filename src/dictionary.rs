@@ -0,0 +1,205 @@
+//! Correction of decoded words with a word list.
+
+use std::collections::{HashMap, HashSet};
+
+use crate::morse_decoder::Code;
+
+/// Common English words and ham radio abbreviations, most common first.
+const WORDS: &str = include_str!("words.txt");
+
+/// Known words, ranked by how common they are.
+///
+/// A decoded word that is not in the dictionary is corrected to the closest known word
+/// reachable by changing how its marks and gaps were classified: flipping a dit into a dah
+/// or back, splitting a letter in two where an element gap was a letter gap, or merging two
+/// letters where a letter gap was an element gap.
+#[derive(Clone, Default, Debug)]
+pub struct Dictionary {
+    /// Rank of each word, 0 for the most common.
+    ranks: HashMap<String, usize>,
+}
+
+impl Dictionary {
+    /// Most changes to the code of a word of at least [`Dictionary::LONG_WORD`] letters.
+    /// Shorter words are corrected with a single change, as two changes turn most short
+    /// words into some other word.
+    pub const MAX_CHANGES: usize = 2;
+    /// Letters in a word for [`Dictionary::MAX_CHANGES`] to apply.
+    pub const LONG_WORD: usize = 6;
+    /// Fewest letters of a word to correct. A single change turns most words of one or
+    /// two letters into another word, such as ET into A.
+    pub const MIN_LETTERS: usize = 3;
+    /// Most letters of a word to correct. The number of candidates grows quickly with the
+    /// length, and longer words are mostly noise.
+    pub const MAX_LETTERS: usize = 12;
+
+    /// The built-in word list.
+    pub fn new() -> Self {
+        let mut dictionary = Self::default();
+        dictionary.add_words(WORDS);
+        dictionary
+    }
+
+    /// The built-in word list, after the words of `path` if it is not empty.
+    pub fn load(path: &str) -> Result<Self, String> {
+        if path.is_empty() {
+            return Ok(Self::new());
+        }
+        let text = std::fs::read_to_string(path)
+            .map_err(|error| format!("Unable to read {path}: {error}."))?;
+        let mut dictionary = Self::default();
+        dictionary.add_words(&text);
+        dictionary.add_words(WORDS);
+        Ok(dictionary)
+    }
+
+    /// Add the words of `text`, one per line, ranked after the known words.
+    /// Empty lines and lines starting with `#` are skipped.
+    pub fn add_words(&mut self, text: &str) {
+        for line in text.lines().map(str::trim) {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let rank = self.ranks.len();
+            self.ranks.entry(line.to_uppercase()).or_insert(rank);
+        }
+    }
+
+    /// Whether `word` is known, ignoring case.
+    pub fn contains(&self, word: &str) -> bool {
+        self.ranks.contains_key(&word.to_uppercase())
+    }
+
+    /// The best correction of a word sent as `letters`, translated by `translate`, or
+    /// [`None`] if the word is known or no known word is close enough. Candidates with
+    /// fewer changes are preferred, then more common words. Words with digits, such as
+    /// call signs and reports, and words shorter than [`Dictionary::MIN_LETTERS`] or longer
+    /// than [`Dictionary::MAX_LETTERS`] are not corrected.
+    pub fn correct(
+        &self,
+        letters: &[Vec<Code>],
+        translate: impl Fn(&[Code]) -> String,
+    ) -> Option<String> {
+        let text = |letters: &[Vec<Code>]| -> String {
+            letters.iter().map(|letter| translate(letter)).collect()
+        };
+        if !(Self::MIN_LETTERS..=Self::MAX_LETTERS).contains(&letters.len()) {
+            return None;
+        }
+        let word = text(letters);
+        if self.contains(&word) || word.chars().any(|x| x.is_ascii_digit()) {
+            return None;
+        }
+        let max_changes = if letters.len() >= Self::LONG_WORD {
+            Self::MAX_CHANGES
+        } else {
+            1
+        };
+
+        // Breadth first search, so the first changes that give known words are the fewest
+        let mut seen: HashSet<Vec<Vec<Code>>> = HashSet::from([letters.to_vec()]);
+        let mut current = vec![letters.to_vec()];
+        for _ in 0..max_changes {
+            let mut next = Vec::new();
+            for candidate in current.iter().flat_map(|letters| changes(letters)) {
+                if seen.insert(candidate.clone()) {
+                    next.push(candidate);
+                }
+            }
+            let best = next
+                .iter()
+                .filter_map(|candidate| {
+                    let text = text(candidate);
+                    self.ranks.get(&text).map(|rank| (*rank, text))
+                })
+                .min();
+            if let Some((_, text)) = best {
+                return Some(text);
+            }
+            current = next;
+        }
+        None
+    }
+}
+
+/// Every code reachable from `letters` by one change of a mark or gap.
+fn changes(letters: &[Vec<Code>]) -> Vec<Vec<Vec<Code>>> {
+    let mut changes = Vec::new();
+    for (i, letter) in letters.iter().enumerate() {
+        // Flip a dit or dah
+        for (j, code) in letter.iter().enumerate() {
+            let flipped = match code {
+                Code::Dit => Code::Dah,
+                Code::Dah => Code::Dit,
+                _ => continue,
+            };
+            let mut change = letters.to_vec();
+            change[i][j] = flipped;
+            changes.push(change);
+        }
+        // Split the letter in two
+        for j in 1..letter.len() {
+            let mut change = letters.to_vec();
+            let second = change[i].split_off(j);
+            change.insert(i + 1, second);
+            changes.push(change);
+        }
+        // Merge with the next letter
+        if i + 1 < letters.len() {
+            let mut change = letters.to_vec();
+            let second = change.remove(i + 1);
+            change[i].extend(second);
+            changes.push(change);
+        }
+    }
+    changes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::alphabet::Alphabet;
+
+    /// The code of each letter of `word`.
+    fn letters(word: &str) -> Vec<Vec<Code>> {
+        let alphabet = Alphabet::latin();
+        word.chars()
+            .map(|x| Code::from_pattern(alphabet.find(&x.to_string(), false).unwrap()))
+            .collect()
+    }
+
+    /// Correct the word sent as the code of `word`.
+    fn correct(word: &str) -> Option<String> {
+        let alphabet = Alphabet::latin();
+        Dictionary::new().correct(&letters(word), |letter| {
+            alphabet.translate(letter, false, false)
+        })
+    }
+
+    #[test]
+    fn corrects_close_words() {
+        assert_eq!(correct("TEDT").as_deref(), Some("TEST"));
+        assert_eq!(correct("TEST"), None);
+        assert_eq!(correct("5NN"), None);
+    }
+
+    #[test]
+    fn keeps_short_words() {
+        assert_eq!(correct("ET"), None);
+        assert_eq!(correct("TEAT"), None);
+    }
+
+    #[test]
+    fn skips_long_words() {
+        assert_eq!(correct("QJXZQJXZQJXZ"), None);
+
+        // Longer words are not even translated
+        let translated = std::cell::Cell::new(0);
+        let correction = Dictionary::new().correct(&letters(&"QJXZ".repeat(12)), |_| {
+            translated.set(translated.get() + 1);
+            String::new()
+        });
+        assert_eq!(correction, None);
+        assert_eq!(translated.get(), 0);
+    }
+}
@@ -0,0 +1,29 @@
+//! Decodes Morse code from a flashing signal.
+//!
+//! Classify each sample of the signal as on or off with [`sensor::SensorSettings`],
+//! feed it to a [`morse_decoder::MorseDecoder`] with
+//! [`morse_decoder::MorseDecoder::sample`] (your own timestamps) or
+//! [`morse_decoder::MorseDecoder::tick`] (the current time), and read the decoded text from
+//! [`morse_decoder::MorseDecoder::transcript`] or the raw [`morse_decoder::Code`]
+//! from [`morse_decoder::MorseDecoder::decode`].
+//!
+//! The transcript is in ITU Latin unless another [`alphabet::Alphabet`] is set with
+//! [`morse_decoder::MorseDecoder::set_alphabet`].
+//!
+//! For audio, [`audio::ToneDetector`] classifies blocks of samples by the level of a tone.
+//!
+//! In the other direction, [`morse_encoder::encode`] turns text into [`morse_decoder::Code`]
+//! and [`morse_encoder::Schedule`] times it as an on/off signal at a given speed, which
+//! [`audio::synthesize`] renders as audio.
+//!
+//! Implement [`signal::SignalSource`] to sample a signal of your own, such as a camera.
+
+#![warn(missing_docs)]
+
+pub mod alphabet;
+pub mod audio;
+pub mod dictionary;
+pub mod morse_decoder;
+pub mod morse_encoder;
+pub mod sensor;
+pub mod signal;
@@ -2,7 +2,7 @@
 
 use std::{
     fs,
-    time::{Duration, SystemTime},
+    time::{Duration, Instant, SystemTime},
 };
 
 use egui::{
@@ -10,92 +10,253 @@ use egui::{
 };
 use screenshots::Screen;
 
-mod morse_decoder;
-use morse_decoder::*;
+use capture::{Capture, CaptureMessage, CaptureSettings};
+use channel::{Channel, ChannelSettings};
+use source::SourceSettings;
+use transmitter::{Transmitter, TransmitterSettings};
+
+mod capture;
+mod channel;
+mod cli;
+mod replay;
+mod source;
+mod transmitter;
+use morse::{
+    audio::{synthesize, write_wav, SynthesisSettings},
+    morse_decoder::*,
+    morse_encoder::Schedule,
+    sensor::*,
+};
 use serde::{Deserialize, Serialize};
 
 fn get_max_size(size: Vec2, max_size: Vec2) -> Vec2 {
-    let mut desired_size = size.clone();
+    let mut desired_size = size;
     desired_size *= (max_size.x / desired_size.x).min(1.0);
     desired_size *= (max_size.y / desired_size.y).min(1.0);
     desired_size
 }
 
-#[derive(Serialize, Deserialize, Default)]
+#[derive(Serialize, Deserialize)]
 struct Config {
-    sensor: SensorSettings,
-    decoder: DecoderSettings,
+    /// Display id of the selected screen.
+    #[serde(default)]
+    screen: Option<u32>,
+    /// Target samples per second of the capture thread.
+    #[serde(default = "default_sample_rate")]
+    sample_rate: f32,
+    /// Where the channels are sampled from.
+    #[serde(default)]
+    source: SourceSettings,
+    #[serde(default)]
+    channels: Vec<ChannelSettings>,
+    #[serde(default)]
+    transmitter: TransmitterSettings,
+    /// Sensor of configuration files from before channels, loaded as the first channel.
+    #[serde(default, skip_serializing)]
+    sensor: Option<SensorSettings>,
+    /// Decoder of configuration files from before channels, loaded as the first channel.
+    #[serde(default, skip_serializing)]
+    decoder: Option<DecoderSettings>,
 }
 
-#[derive(Serialize, Deserialize, Clone, Copy)]
-struct SensorSettings {
-    on_color: (u8, u8, u8),
-    off_color: (u8, u8, u8),
-    on_threshold: f32,
+fn default_sample_rate() -> f32 {
+    200.0
 }
 
-impl Default for SensorSettings {
+impl Default for Config {
     fn default() -> Self {
         Self {
-            on_color: (255, 255, 255),
-            off_color: (255, 255, 255),
-            on_threshold: 0.5,
+            screen: None,
+            sample_rate: default_sample_rate(),
+            source: SourceSettings::Screen,
+            channels: vec![ChannelSettings::new("Sensor 1".to_owned())],
+            transmitter: TransmitterSettings::default(),
+            sensor: None,
+            decoder: None,
         }
     }
 }
 
-fn lerp(x: f32, a: u8, b: u8) -> u8 {
-    let min = a.min(b);
-    let max = a.max(b);
-    if x <= 0.0 {
-        min
-    } else if x >= 1.0 {
-        max
-    } else {
-        ((max - min) as f32 * x) as u8 + min
+impl Config {
+    /// Move a single sensor configuration into [`Config::channels`],
+    /// and make sure there is at least one channel.
+    fn upgrade(mut self) -> Self {
+        if self.channels.is_empty() {
+            let mut channel = ChannelSettings::new("Sensor 1".to_owned());
+            channel.sensor = self.sensor.take().unwrap_or_default();
+            channel.decoder = self.decoder.take().unwrap_or_default();
+            self.channels.push(channel);
+        }
+        self
     }
 }
 
-fn lerp3(x: f32, a: (u8, u8, u8), b: (u8, u8, u8)) -> (u8, u8, u8) {
-    (lerp(x, a.0, b.0), lerp(x, a.1, b.1), lerp(x, a.2, b.2))
+/// Capture the pixels of the sensor region.
+fn capture_sensor(sensor: &SensorSettings, screen: &Screen) -> Result<Vec<(u8, u8, u8)>, String> {
+    match screen.capture_area(
+        sensor.position.0.try_into().unwrap_or(i32::MAX),
+        sensor.position.1.try_into().unwrap_or(i32::MAX),
+        sensor.size.0.max(1),
+        sensor.size.1.max(1),
+    ) {
+        Ok(image) => Ok(rgba_pixels(image.rgba())),
+        Err(error) => Err(format!("Error capturing screen: {error}.")),
+    }
 }
 
-fn inverse_lerp(x: u8, a: u8, b: u8) -> f32 {
-    let min = a.min(b);
-    let max = a.max(b);
-    if x <= min {
-        0.0
-    } else if x >= max {
-        1.0
-    } else {
-        (x - min) as f32 / (max - min) as f32
+/// Draw the sensor color, the off, threshold and on colors, and where the
+/// sensor value falls between the off and on colors.
+/// `on` is the classification of the reading.
+fn sensor_indicator(ui: &mut egui::Ui, sensor: &SensorSettings, reading: Reading, on: bool) {
+    let rgb = reading.color;
+    let threshold_color = lerp3(sensor.on_threshold, sensor.off_color, sensor.on_color);
+
+    let (response, painter) = ui.allocate_painter(Vec2::new(150.0, 100.0), egui::Sense::hover());
+    let x = response.rect.min.x;
+    let y = response.rect.min.y;
+
+    // Sensor color
+    painter.rect_filled(
+        Rect::from_min_size(Pos2::new(x, y), Vec2::new(150.0, 50.0)),
+        0.0,
+        Color32::from_rgb(rgb.0, rgb.1, rgb.2),
+    );
+
+    // Off Color
+    painter.rect_filled(
+        Rect::from_min_size(Pos2::new(x, y + 50.0), Vec2::new(50.0, 50.0)),
+        0.0,
+        Color32::from_rgb(sensor.off_color.0, sensor.off_color.1, sensor.off_color.2),
+    );
+
+    // Threshold Color
+    painter.rect_filled(
+        Rect::from_min_size(Pos2::new(x + 50.0, y + 50.0), Vec2::new(50.0, 50.0)),
+        0.0,
+        Color32::from_rgb(threshold_color.0, threshold_color.1, threshold_color.2),
+    );
+
+    // On Color
+    painter.rect_filled(
+        Rect::from_min_size(Pos2::new(x + 100.0, y + 50.0), Vec2::new(50.0, 50.0)),
+        0.0,
+        Color32::from_rgb(sensor.on_color.0, sensor.on_color.1, sensor.on_color.2),
+    );
+
+    // Thresholds
+    let f = reading.value;
+    let off_threshold = sensor.off_threshold();
+    if off_threshold < sensor.on_threshold {
+        painter.line_segment(
+            [
+                Pos2::new(x + 150.0 * off_threshold, y),
+                Pos2::new(x + 150.0 * off_threshold, y + 100.0),
+            ],
+            egui::Stroke::new(5.0, Color32::DARK_GRAY),
+        );
     }
+    painter.line_segment(
+        [
+            Pos2::new(x + 150.0 * sensor.on_threshold, y),
+            Pos2::new(x + 150.0 * sensor.on_threshold, y + 100.0),
+        ],
+        egui::Stroke::new(5.0, Color32::GRAY),
+    );
+    painter.line_segment(
+        [
+            Pos2::new(x + 150.0 * f, y),
+            Pos2::new(x + 150.0 * f, y + 100.0),
+        ],
+        egui::Stroke::new(5.0, if on { Color32::GREEN } else { Color32::RED }),
+    );
 }
 
-fn inverse_lerp3(x: (u8, u8, u8), a: (u8, u8, u8), b: (u8, u8, u8)) -> f32 {
-    (inverse_lerp(x.0, a.0, b.0) + inverse_lerp(x.1, a.1, b.1) + inverse_lerp(x.2, a.2, b.2)) / 3.0
+/// Select the signal source and edit its settings.
+fn source_settings(ui: &mut egui::Ui, source: &mut SourceSettings) {
+    ui.horizontal(|ui| {
+        ui.label("Source:");
+        let screen = matches!(source, SourceSettings::Screen);
+        if ui.radio(screen, "Screen").clicked() && !screen {
+            *source = SourceSettings::Screen;
+        }
+        let frames = matches!(source, SourceSettings::Frames { .. });
+        if ui.radio(frames, "Frames").clicked() && !frames {
+            *source = SourceSettings::Frames {
+                directory: String::new(),
+                fps: 30.0,
+                timestamps: String::new(),
+            };
+        }
+        let audio = matches!(source, SourceSettings::Audio { .. });
+        if ui.radio(audio, "Audio").clicked() && !audio {
+            *source = SourceSettings::Audio {
+                path: String::new(),
+            };
+        }
+        let synthetic = matches!(source, SourceSettings::Synthetic { .. });
+        if ui.radio(synthetic, "Synthetic").clicked() && !synthetic {
+            *source = SourceSettings::Synthetic {
+                text: "PARIS".to_owned(),
+                wpm: 20.0,
+            };
+        }
+    });
+    egui::Grid::new("source settings").show(ui, |ui| match source {
+        SourceSettings::Screen => {}
+        SourceSettings::Frames {
+            directory,
+            fps,
+            timestamps,
+        } => {
+            ui.label("Frame Directory");
+            ui.text_edit_singleline(directory);
+            ui.end_row();
+            ui.label("Frame Rate (fps)");
+            ui.add(DragValue::new(fps).clamp_range(0.1..=1000.0));
+            ui.end_row();
+            ui.label("Timestamp File");
+            ui.text_edit_singleline(timestamps).on_hover_text(
+                "Time of each frame in seconds, one per line. Leave empty to use the frame rate.",
+            );
+        }
+        SourceSettings::Audio { path } => {
+            ui.label("WAV File");
+            ui.text_edit_singleline(path);
+        }
+        SourceSettings::Synthetic { text, wpm } => {
+            ui.label("Text");
+            ui.text_edit_singleline(text);
+            ui.end_row();
+            ui.label("Speed (WPM)");
+            ui.add(DragValue::new(wpm).clamp_range(1.0..=100.0));
+        }
+    });
 }
 
-/// Get the path of the configuration file path.
-/// [this executable's directory]/config.toml
-fn get_config_file_path() -> Result<std::path::PathBuf, String> {
+/// Get the path of a file beside this executable.
+/// [this executable's directory]/[file_name]
+fn get_executable_file_path(file_name: &str) -> Result<std::path::PathBuf, String> {
     match std::env::current_exe() {
-        Err(exe_path_error) => {
-            return Err(format!(
-                "Unable to obtain executable directory: {exe_path_error}."
-            ))
-        }
+        Err(exe_path_error) => Err(format!(
+            "Unable to obtain executable directory: {exe_path_error}."
+        )),
         Ok(exe_path) => match exe_path.parent() {
-            None => return Err("Unable to obtain executable directory.".to_string()),
-            Some(parent_dir) => Ok(parent_dir.join("config.toml")),
+            None => Err("Unable to obtain executable directory.".to_string()),
+            Some(parent_dir) => Ok(parent_dir.join(file_name)),
         },
     }
 }
 
+/// Get the path of the configuration file path.
+/// [this executable's directory]/config.toml
+fn get_config_file_path() -> Result<std::path::PathBuf, String> {
+    get_executable_file_path("config.toml")
+}
+
 /// Load the toml configuration from [`get_config_file_path`].
 fn load_config() -> Result<Config, String> {
     let config_file_path = get_config_file_path()?;
-    println!(
+    eprintln!(
         "[Configuration Loader] Loading configuration file \"{}\".",
         config_file_path.display()
     );
@@ -103,39 +264,39 @@ fn load_config() -> Result<Config, String> {
     match fs::read_to_string(&config_file_path) {
         Ok(config_data) => match toml::from_str(&config_data) {
             Err(error) => {
-                println!(
+                eprintln!(
                     "[Configuration Loader] Unable to deserialize configuration file: {error}."
                 );
                 Err(format!(
                     "Unable to deserialize configuration file: {error}."
                 ))
             }
-            Ok(config) => Ok(config),
+            Ok(config) => Ok(Config::upgrade(config)),
         },
         Err(read_error) => {
-            println!("Unable to open configuration file: {read_error}. Installing default.");
+            eprintln!("Unable to open configuration file: {read_error}. Installing default.");
             if let Err(write_error) = fs::write(
                 &config_file_path,
                 toml::to_string_pretty(&Config::default()).unwrap(),
             ) {
-                println!("[Configuration Loader] Unable to install default configuration file: {write_error}.");
+                eprintln!("[Configuration Loader] Unable to install default configuration file: {write_error}.");
                 return Err(format!(
                     "Unable to install default configuration file: {write_error}."
                 ));
             }
             match fs::read_to_string(&config_file_path) {
                 Err(read_error) => {
-                    println!("[Configuration Loader] Unable to open newly created configuration file: {read_error}.");
-                    return Err(format!(
+                    eprintln!("[Configuration Loader] Unable to open newly created configuration file: {read_error}.");
+                    Err(format!(
                         "Unable to open newly created configuration file: {read_error}."
-                    ));
+                    ))
                 }
                 Ok(serialized_config) => match toml::from_str(&serialized_config) {
                     Err(deserialize_error) => {
-                        println!("[Configuration Loader] Unable to deserialize default configuration file: {deserialize_error}.");
+                        eprintln!("[Configuration Loader] Unable to deserialize default configuration file: {deserialize_error}.");
                         Err(format!("Unable to deserialize default configuration file: {deserialize_error}."))
                     }
-                    Ok(config) => Ok(config),
+                    Ok(config) => Ok(Config::upgrade(config)),
                 },
             }
         }
@@ -147,18 +308,18 @@ fn load_config() -> Result<Config, String> {
 fn save_config(config: &Config) -> Result<bool, String> {
     match toml::to_string_pretty(config) {
         Err(error) => {
-            println!("[Configuration Saver] Unable to serialize configuration file: {error}.");
+            eprintln!("[Configuration Saver] Unable to serialize configuration file: {error}.");
             Err(format!("Unable to serialize configuration file: {error}."))
         }
         Ok(serialized_config) => {
             let config_file_path = get_config_file_path()?;
-            println!(
+            eprintln!(
                 "[Configuration Saver] Saving configuration file \"{}\".",
                 config_file_path.display()
             );
             match fs::write(&config_file_path, serialized_config) {
                 Err(error) => {
-                    println!("[Configuration Saver] Unable to write configuration file: {error}.");
+                    eprintln!("[Configuration Saver] Unable to write configuration file: {error}.");
                     Err(format!("Unable to write configuration file: {error}."))
                 }
                 Ok(_) => Ok(true),
@@ -167,6 +328,56 @@ fn save_config(config: &Config) -> Result<bool, String> {
     }
 }
 
+/// Save each channel's decoded text, preceded by its name and estimated speed, to
+/// [this executable's directory]/transcript.txt.
+fn save_transcript(channels: &[Channel]) -> Result<bool, String> {
+    let transcript_file_path = get_executable_file_path("transcript.txt")?;
+    eprintln!(
+        "[Transcript Saver] Saving transcript \"{}\".",
+        transcript_file_path.display()
+    );
+    let mut transcript = String::new();
+    for channel in channels {
+        let speed = match channel.decoder.speed() {
+            Some(speed) => speed.to_string(),
+            None => "Unknown".to_owned(),
+        };
+        transcript += &format!(
+            "{}\nSpeed: {speed}\n\n{}\n\n",
+            channel.settings.name,
+            channel.decoder.transcript()
+        );
+    }
+    match fs::write(&transcript_file_path, transcript) {
+        Err(error) => {
+            eprintln!("[Transcript Saver] Unable to write transcript: {error}.");
+            Err(format!("Unable to write transcript: {error}."))
+        }
+        Ok(_) => Ok(true),
+    }
+}
+
+/// Save `schedule` as Morse code audio to [this executable's directory]/[file_name].
+fn save_audio(
+    file_name: &str,
+    schedule: &Schedule,
+    settings: &SynthesisSettings,
+) -> Result<bool, String> {
+    let audio_file_path = get_executable_file_path(file_name)?;
+    eprintln!(
+        "[Audio Saver] Saving audio \"{}\".",
+        audio_file_path.display()
+    );
+    let samples = synthesize(schedule, settings);
+    match write_wav(&audio_file_path, &samples, settings.sample_rate) {
+        Err(error) => {
+            eprintln!("[Audio Saver] {error}");
+            Err(error)
+        }
+        Ok(_) => Ok(true),
+    }
+}
+
 struct Morse {
     painter: egui::Painter,
     message: RichText,
@@ -176,32 +387,169 @@ struct Morse {
     preview: Option<(TextureHandle, Vec<u8>)>,
     frame_width: u32,
     frame_height: u32,
-    sensor_position: (u32, u32, usize),
-    sensor_settings: SensorSettings,
-    decoder_settings: DecoderSettings,
-    decoder: MorseDecoder,
+    channels: Vec<Channel>,
+    /// Channel edited in the main panel and preview.
+    selected_channel: usize,
     recording_window: bool,
-    recording: bool,
+    transmitter: Transmitter,
+    transmitter_window: bool,
+    /// Reference point for sample times.
+    epoch: Instant,
+    sample_rate: f32,
+    source: SourceSettings,
+    capture: Option<Capture>,
+    /// Achieved sample rate and jitter from the capture thread.
+    capture_status: Option<(f32, f32)>,
+    /// Preview pixel where the current sensor region drag started.
+    drag_start: Option<(u32, u32)>,
 }
 
 impl Morse {
     const MAX_FRAME_DELAY: Duration = Duration::from_millis(20);
 
-    fn new(cc: &eframe::CreationContext<'_>) -> Self {
-        // Load config
-        let (m1, e1, sensor_settings, decoder_settings) = match load_config() {
-            Ok(config) => (
-                "Loaded config.toml.".to_owned(),
-                false,
-                config.sensor,
-                config.decoder,
+    /// Sensor settings of the selected channel.
+    fn sensor_settings(&mut self) -> &mut SensorSettings {
+        &mut self.channels[self.selected_channel].settings.sensor
+    }
+
+    /// The color of the selected channel's sensor region in the preview.
+    fn preview_color(&self) -> Option<(u8, u8, u8)> {
+        let preview = self.preview.as_ref()?;
+        let sensor = &self.channels[self.selected_channel].settings.sensor;
+        let (x, y) = sensor.position;
+        let (width, height) = sensor.size;
+        let x_end = x.saturating_add(width.max(1)).min(self.frame_width);
+        let y_end = y.saturating_add(height.max(1)).min(self.frame_height);
+        let mut pixels = Vec::new();
+        for row in y..y_end {
+            let i = (row as usize * self.frame_width as usize + x as usize) * 4;
+            let j = (row as usize * self.frame_width as usize + x_end as usize) * 4;
+            pixels.extend(rgba_pixels(preview.1.get(i..j)?));
+        }
+        (!pixels.is_empty()).then(|| sensor.read(&pixels).color)
+    }
+
+    /// The preview pixel under `position`, a point within `rect`.
+    fn preview_pixel(&self, position: Pos2, rect: Rect) -> (u32, u32) {
+        let x = remap_clamp(
+            position.x,
+            rect.min.x..=rect.max.x,
+            0.0..=self.frame_width as f32,
+        )
+        .floor() as u32;
+        let y = remap_clamp(
+            position.y,
+            rect.min.y..=rect.max.y,
+            0.0..=self.frame_height as f32,
+        )
+        .floor() as u32;
+        (
+            x.min(self.frame_width.saturating_sub(1)),
+            y.min(self.frame_height.saturating_sub(1)),
+        )
+    }
+
+    /// The point within `rect` displaying the preview pixel `pixel`.
+    fn preview_position(&self, pixel: (u32, u32), rect: Rect) -> Pos2 {
+        Pos2::new(
+            remap_clamp(
+                pixel.0 as f32,
+                0.0..=self.frame_width as f32,
+                rect.min.x..=rect.max.x,
             ),
-            Err(error) => (
-                error,
-                true,
-                SensorSettings::default(),
-                DecoderSettings::default(),
+            remap_clamp(
+                pixel.1 as f32,
+                0.0..=self.frame_height as f32,
+                rect.min.y..=rect.max.y,
             ),
+        )
+    }
+
+    /// Largest size of a sensor region: the size of the preview, or else of the selected
+    /// screen in pixels.
+    fn max_region_size(&self) -> (u32, u32) {
+        if self.preview.is_some() {
+            return (self.frame_width.max(1), self.frame_height.max(1));
+        }
+        self.screens
+            .get(self.selected_screen)
+            .map_or((u32::MAX, u32::MAX), |screen| {
+                let info = &screen.display_info;
+                let pixels = |size: u32| ((size as f32 * info.scale_factor).ceil() as u32).max(1);
+                (pixels(info.width), pixels(info.height))
+            })
+    }
+
+    /// Settings for the capture thread from the current channels.
+    fn capture_settings(&self) -> CaptureSettings {
+        CaptureSettings {
+            channels: self
+                .channels
+                .iter()
+                .map(|channel| channel.settings.source())
+                .collect(),
+            sample_rate: self.sample_rate,
+        }
+    }
+
+    /// Feed the messages of the capture thread to the channels and retune adaptive ones,
+    /// whether or not the recording window is open, and stop capturing when the source
+    /// ends or fails.
+    fn receive_capture(&mut self) {
+        let mut stop = false;
+        if let Some(capture) = &self.capture {
+            *capture.settings.lock().unwrap() = self.capture_settings();
+            for message in capture.receiver.try_iter() {
+                match message {
+                    CaptureMessage::Transition { channel, time, on } => {
+                        if let Some(channel) = self.channels.get_mut(channel) {
+                            channel.decoder.sample(on, time, &channel.settings.decoder);
+                        }
+                    }
+                    CaptureMessage::Status {
+                        time,
+                        readings,
+                        sample_rate,
+                        jitter,
+                    } => {
+                        for (channel, (on, reading)) in self.channels.iter_mut().zip(readings) {
+                            channel.decoder.sample(on, time, &channel.settings.decoder);
+                            channel.update_reading(on, reading);
+                        }
+                        self.capture_status = Some((sample_rate, jitter));
+                    }
+                    CaptureMessage::End { time } => {
+                        for channel in &mut self.channels {
+                            channel.decoder.finish(time, &channel.settings.decoder);
+                        }
+                        self.message =
+                            RichText::new("The recording has ended.").color(Color32::GREEN);
+                        stop = true;
+                    }
+                    CaptureMessage::Error(error) => {
+                        self.message = RichText::new(error).monospace().color(Color32::RED);
+                        stop = true;
+                    }
+                }
+            }
+
+            // Adaptive timing
+            for channel in &mut self.channels {
+                if channel.settings.decoder.adaptive {
+                    channel.decoder.adapt(&mut channel.settings.decoder);
+                }
+            }
+        }
+        if stop {
+            self.capture = None;
+        }
+    }
+
+    fn new(cc: &eframe::CreationContext<'_>) -> Self {
+        // Load config
+        let (m1, e1, config) = match load_config() {
+            Ok(config) => ("Loaded config.toml.".to_owned(), false, config),
+            Err(error) => (error, true, Config::default()),
         };
 
         // Get screens
@@ -218,6 +566,12 @@ impl Morse {
             ),
         };
 
+        // Select the configured screen
+        let selected_screen = screens
+            .iter()
+            .position(|screen| Some(screen.display_info.id) == config.screen)
+            .unwrap_or(9999);
+
         // Compile message
         let message = RichText::new(m1 + &m2).color(if e1 || e2 {
             Color32::RED
@@ -233,17 +587,22 @@ impl Morse {
             )),
             message,
             screens,
-            selected_screen: 9999,
+            selected_screen,
             last_time: SystemTime::now(),
             preview: None,
             frame_width: 10,
             frame_height: 10,
-            sensor_position: (0, 0, 0),
-            sensor_settings,
-            decoder_settings,
-            decoder: MorseDecoder::new(),
+            channels: config.channels.into_iter().map(Channel::new).collect(),
+            selected_channel: 0,
             recording_window: false,
-            recording: false,
+            transmitter: Transmitter::new(config.transmitter),
+            transmitter_window: false,
+            epoch: Instant::now(),
+            sample_rate: config.sample_rate,
+            source: config.source,
+            capture: None,
+            capture_status: None,
+            drag_start: None,
         }
     }
 }
@@ -258,6 +617,8 @@ impl eframe::App for Morse {
             .as_millis();
         self.last_time = now;
 
+        self.receive_capture();
+
         // Set ui style
         let mut style: egui::Style = (*ctx.style()).clone();
         style.override_text_style = Some(egui::TextStyle::Monospace);
@@ -277,240 +638,269 @@ impl eframe::App for Morse {
             // Save config.toml
             if ui.button("Save config.toml").clicked() {
                 if let Err(error) = save_config(&Config {
-                    sensor: self.sensor_settings,
-                    decoder: self.decoder_settings,
+                    screen: self
+                        .screens
+                        .get(self.selected_screen)
+                        .map(|screen| screen.display_info.id),
+                    sample_rate: self.sample_rate,
+                    source: self.source.clone(),
+                    channels: self
+                        .channels
+                        .iter()
+                        .map(|channel| channel.settings.clone())
+                        .collect(),
+                    transmitter: self.transmitter.settings.clone(),
+                    sensor: None,
+                    decoder: None,
                 }) {
                     self.message = RichText::new(error).color(Color32::RED);
                 }
             }
 
+            // Channel selection
+            ui.horizontal(|ui| {
+                ui.label("Channel:");
+                for (i, channel) in self.channels.iter().enumerate() {
+                    ui.radio_value(&mut self.selected_channel, i, &channel.settings.name);
+                }
+            });
+            ui.horizontal(|ui| {
+                let recording = self.capture.is_some();
+                if ui
+                    .add_enabled(!recording, Button::new("Add Channel"))
+                    .clicked()
+                {
+                    let name = format!("Sensor {}", self.channels.len() + 1);
+                    self.channels.push(Channel::new(ChannelSettings::new(name)));
+                    self.selected_channel = self.channels.len() - 1;
+                }
+                if ui
+                    .add_enabled(
+                        !recording && self.channels.len() > 1,
+                        Button::new("Remove Channel"),
+                    )
+                    .clicked()
+                {
+                    self.channels.remove(self.selected_channel);
+                    self.selected_channel = self.selected_channel.saturating_sub(1);
+                }
+                ui.label("Name:");
+                ui.text_edit_singleline(&mut self.channels[self.selected_channel].settings.name);
+            });
+
             // Recognition settings
-            ui.label(format!(
-                "Sensor Position: ({}, {})",
-                self.sensor_position.0, self.sensor_position.1
-            ));
+            let preview_color = self.preview_color();
+            let (max_width, max_height) = self.max_region_size();
+            let sensor = self.sensor_settings();
+            sensor.size = (
+                sensor.size.0.min(max_width),
+                sensor.size.1.min(max_height),
+            );
+            ui.horizontal(|ui| {
+                ui.label("Sensor Region:");
+                ui.add(DragValue::new(&mut sensor.position.0).prefix("x "));
+                ui.add(DragValue::new(&mut sensor.position.1).prefix("y "));
+                ui.add(
+                    DragValue::new(&mut sensor.size.0)
+                        .prefix("w ")
+                        .clamp_range(1..=max_width),
+                );
+                ui.add(
+                    DragValue::new(&mut sensor.size.1)
+                        .prefix("h ")
+                        .clamp_range(1..=max_height),
+                );
+            });
+            ui.horizontal(|ui| {
+                ui.label("Aggregate:");
+                ui.radio_value(&mut sensor.aggregate, Aggregate::Mean, "Mean");
+                ui.radio_value(&mut sensor.aggregate, Aggregate::Median, "Median");
+                ui.radio_value(&mut sensor.aggregate, Aggregate::Fraction, "Fraction On");
+            });
+            if sensor.aggregate == Aggregate::Fraction {
+                ui.add(
+                    egui::Slider::new(&mut sensor.pixel_threshold, 0.0..=1.0)
+                        .text("Pixel Threshold"),
+                )
+                .on_hover_text("Pixels at or above this value are on. The on and off thresholds apply to the fraction of pixels that are on.");
+            }
+            ui.horizontal(|ui| {
+                ui.label("Color Mode:");
+                egui::ComboBox::from_id_source("color mode")
+                    .selected_text(format!("{:?}", sensor.mode))
+                    .show_ui(ui, |ui| {
+                        for (mode, text) in [
+                            (ColorMode::Average, "Average (RGB channels)"),
+                            (ColorMode::Luminance, "Luminance"),
+                            (ColorMode::Lab, "Lab (nearest color)"),
+                            (ColorMode::Projection, "Projection (RGB)"),
+                            (ColorMode::Red, "Red"),
+                            (ColorMode::Green, "Green"),
+                            (ColorMode::Blue, "Blue"),
+                            (ColorMode::Hue, "Hue"),
+                        ] {
+                            ui.selectable_value(&mut sensor.mode, mode, text);
+                        }
+                    });
+            });
 
             if ui
                 .add(
                     Button::new(RichText::new("Set On Color").color(Color32::from_rgb(
-                        255 - self.sensor_settings.on_color.0,
-                        255 - self.sensor_settings.on_color.1,
-                        255 - self.sensor_settings.on_color.2,
+                        255 - sensor.on_color.0,
+                        255 - sensor.on_color.1,
+                        255 - sensor.on_color.2,
                     )))
                     .fill(Color32::from_rgb(
-                        self.sensor_settings.on_color.0,
-                        self.sensor_settings.on_color.1,
-                        self.sensor_settings.on_color.2,
+                        sensor.on_color.0,
+                        sensor.on_color.1,
+                        sensor.on_color.2,
                     )),
                 )
                 .clicked()
             {
-                if let Some(preview) = &self.preview {
-                    self.sensor_settings.on_color = (
-                        preview.1[self.sensor_position.2],
-                        preview.1[self.sensor_position.2 + 1],
-                        preview.1[self.sensor_position.2 + 2],
-                    );
+                if let Some(color) = preview_color {
+                    sensor.on_color = color;
                 }
             }
             if ui
                 .add(
                     Button::new(RichText::new("Set Off Color").color(Color32::from_rgb(
-                        255 - self.sensor_settings.off_color.0,
-                        255 - self.sensor_settings.off_color.1,
-                        255 - self.sensor_settings.off_color.2,
+                        255 - sensor.off_color.0,
+                        255 - sensor.off_color.1,
+                        255 - sensor.off_color.2,
                     )))
                     .fill(Color32::from_rgb(
-                        self.sensor_settings.off_color.0,
-                        self.sensor_settings.off_color.1,
-                        self.sensor_settings.off_color.2,
+                        sensor.off_color.0,
+                        sensor.off_color.1,
+                        sensor.off_color.2,
                     )),
                 )
                 .clicked()
             {
-                if let Some(preview) = &self.preview {
-                    self.sensor_settings.off_color = (
-                        preview.1[self.sensor_position.2],
-                        preview.1[self.sensor_position.2 + 1],
-                        preview.1[self.sensor_position.2 + 2],
-                    );
+                if let Some(color) = preview_color {
+                    sensor.off_color = color;
                 }
             }
 
-            // Recording window
-            if ui.button("Recording").clicked() {
-                self.recording_window = true;
-            }
+            // Recording and transmitter windows
+            ui.horizontal(|ui| {
+                if ui.button("Recording").clicked() {
+                    self.recording_window = true;
+                }
+                if ui.button("Transmitter").clicked() {
+                    self.transmitter_window = true;
+                }
+            });
+
+            let channel = &self.channels[self.selected_channel];
+            egui::Window::new("Transmitter")
+                .open(&mut self.transmitter_window)
+                .show(ctx, |ui| {
+                    if let Err(error) = self.transmitter.ui(ui, channel) {
+                        self.message = RichText::new(error).color(Color32::RED);
+                    }
+                });
 
             let mut recording_window = self.recording_window;
             egui::Window::new("Recording")
                 .open(&mut recording_window)
                 .show(ctx, |ui| {
+                    // Source
+                    ui.add_enabled_ui(self.capture.is_none(), |ui| {
+                        source_settings(ui, &mut self.source)
+                    });
+
                     // Start/stop recording
                     if ui
-                        .button(if self.recording {
+                        .button(if self.capture.is_some() {
                             "Stop Recording"
                         } else {
                             "Start Recording"
                         })
                         .clicked()
                     {
-                        self.recording ^= true;
-                    }
-
-                    // Reset
-                    if ui.button("Reset").clicked() {
-                        self.decoder.reset();
-                    }
-
-                    // Sensor
-                    if self.recording {
-                        if let Some(screen) = self.screens.get(self.selected_screen) {
-                            match screen.capture_area(
-                                self.sensor_position.0.try_into().unwrap(),
-                                self.sensor_position.1.try_into().unwrap(),
-                                1,
-                                1,
-                            ) {
-                                Ok(image) => {
-                                    let rgba = image.rgba();
-                                    let rgb = (rgba[0], rgba[1], rgba[2]);
-                                    let threshold_color = lerp3(
-                                        self.sensor_settings.on_threshold,
-                                        self.sensor_settings.off_color,
-                                        self.sensor_settings.on_color,
-                                    );
-
-                                    let (response, painter) = ui.allocate_painter(
-                                        Vec2::new(150.0, 100.0),
-                                        egui::Sense::hover(),
-                                    );
-                                    let x = response.rect.min.x;
-                                    let y = response.rect.min.y;
-
-                                    // Sensor color
-                                    painter.rect_filled(
-                                        Rect::from_min_size(
-                                            Pos2::new(x, y),
-                                            Vec2::new(150.0, 50.0),
-                                        ),
-                                        0.0,
-                                        Color32::from_rgb(rgb.0, rgb.1, rgb.2),
-                                    );
-
-                                    // Off Color
-                                    painter.rect_filled(
-                                        Rect::from_min_size(
-                                            Pos2::new(x, y + 50.0),
-                                            Vec2::new(50.0, 50.0),
-                                        ),
-                                        0.0,
-                                        Color32::from_rgb(
-                                            self.sensor_settings.off_color.0,
-                                            self.sensor_settings.off_color.1,
-                                            self.sensor_settings.off_color.2,
-                                        ),
-                                    );
-
-                                    // Threshold Color
-                                    painter.rect_filled(
-                                        Rect::from_min_size(
-                                            Pos2::new(x + 50.0, y + 50.0),
-                                            Vec2::new(50.0, 50.0),
-                                        ),
-                                        0.0,
-                                        Color32::from_rgb(
-                                            threshold_color.0,
-                                            threshold_color.1,
-                                            threshold_color.2,
-                                        ),
-                                    );
-
-                                    // On Color
-                                    painter.rect_filled(
-                                        Rect::from_min_size(
-                                            Pos2::new(x + 100.0, y + 50.0),
-                                            Vec2::new(50.0, 50.0),
-                                        ),
-                                        0.0,
-                                        Color32::from_rgb(
-                                            self.sensor_settings.on_color.0,
-                                            self.sensor_settings.on_color.1,
-                                            self.sensor_settings.on_color.2,
-                                        ),
-                                    );
-
-                                    // Threshold
-                                    let f = inverse_lerp3(
-                                        rgb,
-                                        self.sensor_settings.off_color,
-                                        self.sensor_settings.on_color,
-                                    );
-                                    painter.line_segment(
-                                        [
-                                            Pos2::new(
-                                                x + 150.0 * self.sensor_settings.on_threshold,
-                                                y,
-                                            ),
-                                            Pos2::new(
-                                                x + 150.0 * self.sensor_settings.on_threshold,
-                                                y + 100.0,
-                                            ),
-                                        ],
-                                        egui::Stroke::new(5.0, Color32::GRAY),
-                                    );
-                                    painter.line_segment(
-                                        [
-                                            Pos2::new(x + 150.0 * f, y),
-                                            Pos2::new(x + 150.0 * f, y + 100.0),
-                                        ],
-                                        egui::Stroke::new(
-                                            5.0,
-                                            if f < self.sensor_settings.on_threshold {
-                                                Color32::RED
-                                            } else {
-                                                Color32::GREEN
-                                            },
-                                        ),
-                                    );
-
-                                    self.decoder.tick(f >= self.sensor_settings.on_threshold);
+                        if self.capture.is_some() {
+                            self.capture = None;
+                        } else {
+                            let screen = self.screens.get(self.selected_screen).copied();
+                            match self.source.open(screen, self.epoch) {
+                                Ok(source) => {
+                                    self.capture_status = None;
+                                    self.capture = Some(Capture::start(
+                                        source,
+                                        self.capture_settings(),
+                                        self.epoch,
+                                    ));
                                 }
                                 Err(error) => {
-                                    self.message =
-                                        RichText::new(format!("Error capturing screen: {error}."))
-                                            .monospace()
-                                            .color(Color32::RED);
+                                    self.message = RichText::new(error).color(Color32::RED);
                                 }
                             }
                         }
                     }
+                    ui.horizontal(|ui| {
+                        ui.label("Sample Rate (Hz)");
+                        ui.add(DragValue::new(&mut self.sample_rate).clamp_range(1.0..=2000.0));
+                    });
 
-                    // Display code
-                    ui.label(Code::display_code_string(
-                        self.decoder.decode(&self.decoder_settings),
-                    ));
+                    // Reset
+                    if ui.button("Reset").clicked() {
+                        for channel in &mut self.channels {
+                            channel.decoder.reset();
+                        }
+                    }
 
-                    // Decoder settings
-                    ui.add(
-                        egui::Slider::new(&mut self.sensor_settings.on_threshold, 0.0..=1.0)
-                            .text("On Threshold"),
-                    );
-                    egui::Grid::new("decoder settings").show(ui, |ui| {
-                        ui.label("Dit/Dah Threshold (ms)");
-                        ui.add(DragValue::new(&mut self.decoder_settings.dit_dah));
-                        ui.end_row();
-                        ui.label("Minimum Letter Gap (ms)");
-                        ui.add(DragValue::new(&mut self.decoder_settings.letter));
-                        ui.end_row();
-                        ui.label("Minimum Word Gap (ms)");
-                        ui.add(DragValue::new(&mut self.decoder_settings.letter_word));
+                    // Export transcript.txt and transcript.wav
+                    ui.horizontal(|ui| {
+                        if ui.button("Export transcript.txt").clicked() {
+                            if let Err(error) = save_transcript(&self.channels) {
+                                self.message = RichText::new(error).color(Color32::RED);
+                            }
+                        }
+                        if ui
+                            .button("Export transcript.wav")
+                            .on_hover_text("Morse code audio of the transcripts with the transmitter's speed and audio settings, in the alphabet of the selected channel.")
+                            .clicked()
+                        {
+                            let text: Vec<&str> = self
+                                .channels
+                                .iter()
+                                .map(|channel| channel.decoder.transcript())
+                                .collect();
+                            let settings = &self.transmitter.settings;
+                            let schedule = self.channels[self.selected_channel]
+                                .schedule(&text.join(" "), &settings.encoder);
+                            if let Err(error) =
+                                save_audio("transcript.wav", &schedule, &settings.audio)
+                            {
+                                self.message = RichText::new(error).color(Color32::RED);
+                            }
+                        }
                     });
 
-                    // Display recorded timings
-                    egui::ScrollArea::vertical().show(ui, |ui| {
-                        ui.label(self.decoder.display());
-                    });
+                    // Sensors
+                    if self.capture.is_none() {
+                        for channel in &mut self.channels {
+                            channel.clear_reading();
+                        }
+                    } else if let Some((sample_rate, jitter)) = self.capture_status {
+                        ui.label(format!(
+                            "Sampling: {sample_rate:.0} Hz, {jitter:.2} ms jitter"
+                        ));
+                    }
+
+                    // Channels
+                    egui::ScrollArea::vertical()
+                        .id_source("channels")
+                        .show(ui, |ui| {
+                            for (i, channel) in self.channels.iter_mut().enumerate() {
+                                egui::CollapsingHeader::new(&channel.settings.name)
+                                    .id_source(("channel", i))
+                                    .default_open(true)
+                                    .show(ui, |ui| channel.ui(ui, i));
+                            }
+                        });
                 });
             self.recording_window = recording_window;
 
@@ -527,7 +917,9 @@ impl eframe::App for Morse {
                     .clicked()
                 {
                     self.preview = None;
-                    self.sensor_position = (0, 0, 0);
+                    for channel in &mut self.channels {
+                        channel.settings.sensor.position = (0, 0);
+                    }
                 }
             }
 
@@ -564,7 +956,7 @@ impl eframe::App for Morse {
                 };
             }
 
-            if let Some(preview) = &mut self.preview {
+            if let Some(preview) = &self.preview {
                 // Display the preview
                 let preview_response = ui
                     .image(
@@ -574,45 +966,65 @@ impl eframe::App for Morse {
                             ui.available_size(),
                         ),
                     )
-                    .interact(egui::Sense::click());
-
-                // Draw sensor circle
-                self.painter.circle_stroke(
-                    Pos2::new(
-                        remap_clamp(
-                            self.sensor_position.0 as f32,
-                            0.0..=self.frame_width as f32,
-                            preview_response.rect.min.x..=preview_response.rect.max.x,
-                        ),
-                        remap_clamp(
-                            self.sensor_position.1 as f32,
-                            0.0..=self.frame_height as f32,
-                            preview_response.rect.min.y..=preview_response.rect.max.y,
+                    .interact(egui::Sense::click_and_drag());
+                let rect = preview_response.rect;
+
+                // Draw sensor regions, highlighting the selected channel
+                for (i, channel) in self.channels.iter().enumerate() {
+                    let (x, y) = channel.settings.sensor.position;
+                    let (width, height) = channel.settings.sensor.size;
+                    let region = Rect::from_min_max(
+                        self.preview_position((x, y), rect),
+                        self.preview_position(
+                            (x.saturating_add(width), y.saturating_add(height)),
+                            rect,
                         ),
-                    ),
-                    10.0,
-                    egui::Stroke::new(2.0, Color32::GREEN),
-                );
+                    );
+                    let color = if i == self.selected_channel {
+                        Color32::GREEN
+                    } else {
+                        Color32::GRAY
+                    };
+                    self.painter
+                        .rect_stroke(region, 0.0, egui::Stroke::new(1.0, color));
+                    self.painter.circle_stroke(
+                        region.center(),
+                        10.0,
+                        egui::Stroke::new(2.0, color),
+                    );
+                    self.painter.text(
+                        region.center() + Vec2::new(12.0, -12.0),
+                        egui::Align2::LEFT_BOTTOM,
+                        &channel.settings.name,
+                        egui::FontId::monospace(12.0),
+                        color,
+                    );
+                }
 
-                // Preview interaction
-                if preview_response.clicked() {
-                    if let Some(screen_position) = preview_response.interact_pointer_pos() {
-                        let x = remap_clamp(
-                            screen_position.x,
-                            preview_response.rect.min.x..=preview_response.rect.max.x,
-                            0.0..=self.frame_width as f32,
-                        )
-                        .floor() as u32;
-                        let y = remap_clamp(
-                            screen_position.y,
-                            preview_response.rect.min.y..=preview_response.rect.max.y,
-                            0.0..=self.frame_height as f32,
-                        )
-                        .floor() as u32;
-                        let i = (y as usize * self.frame_width as usize + x as usize) * 4;
-                        self.sensor_position = (x, y, i);
+                // Preview interaction, click to move the region or drag to select a new one
+                let pointer_pixel = preview_response
+                    .interact_pointer_pos()
+                    .map(|position| self.preview_pixel(position, rect));
+                if let Some((x, y)) = pointer_pixel {
+                    if preview_response.drag_started() {
+                        self.drag_start = Some((x, y));
+                    }
+                    let drag_start = self.drag_start;
+                    let sensor = self.sensor_settings();
+                    if let Some(start) = drag_start {
+                        sensor.position = (start.0.min(x), start.1.min(y));
+                        sensor.size = (start.0.abs_diff(x) + 1, start.1.abs_diff(y) + 1);
+                    }
+                    if preview_response.clicked() {
+                        sensor.position = (
+                            x.saturating_sub(sensor.size.0 / 2),
+                            y.saturating_sub(sensor.size.1 / 2),
+                        );
                     }
                 }
+                if preview_response.drag_released() {
+                    self.drag_start = None;
+                }
             }
         });
 
@@ -621,8 +1033,24 @@ impl eframe::App for Morse {
 }
 
 fn main() {
-    let mut native_options = eframe::NativeOptions::default();
-    native_options.min_window_size = Some(Vec2::new(850.0, 500.0));
+    let mut args = std::env::args().skip(1);
+    let result = match args.next().as_deref() {
+        Some("decode") => Some(cli::run(args)),
+        Some("encode") => Some(cli::encode(args)),
+        _ => None,
+    };
+    if let Some(result) = result {
+        if let Err(error) = result {
+            eprintln!("{error}");
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    let native_options = eframe::NativeOptions {
+        min_window_size: Some(Vec2::new(850.0, 500.0)),
+        ..Default::default()
+    };
     let _ = eframe::run_native(
         "Morse",
         native_options,
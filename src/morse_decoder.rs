@@ -1,33 +1,275 @@
-use std::time::SystemTime;
+//! Classification of mark and gap durations into Morse code and text.
+
+use std::{
+    collections::BTreeSet,
+    time::{Duration, Instant},
+};
 
 use serde::{Deserialize, Serialize};
 
+use crate::{
+    alphabet::{Alphabet, AlphabetSettings},
+    dictionary::Dictionary,
+};
+
+/// A decoded Morse code element.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
 pub enum Code {
+    /// Short mark.
     Dit,
+    /// Long mark.
     Dah,
+    /// Long dash of American Morse (L), twice as long as a dah.
+    LongDah,
+    /// Longer dash of American Morse (0), three times as long as a dah.
+    ExtraLongDah,
+    /// Space within an American Morse character, such as in C (`.. .`).
+    Space,
+    /// Gap between letters.
     Short,
+    /// Gap between words.
     Long,
 }
 
+/// ITU letters as (dit/dah pattern, character) pairs.
+pub(crate) const LETTERS: [(&str, char); 26] = [
+    (".-", 'A'),
+    ("-...", 'B'),
+    ("-.-.", 'C'),
+    ("-..", 'D'),
+    (".", 'E'),
+    ("..-.", 'F'),
+    ("--.", 'G'),
+    ("....", 'H'),
+    ("..", 'I'),
+    (".---", 'J'),
+    ("-.-", 'K'),
+    (".-..", 'L'),
+    ("--", 'M'),
+    ("-.", 'N'),
+    ("---", 'O'),
+    (".--.", 'P'),
+    ("--.-", 'Q'),
+    (".-.", 'R'),
+    ("...", 'S'),
+    ("-", 'T'),
+    ("..-", 'U'),
+    ("...-", 'V'),
+    (".--", 'W'),
+    ("-..-", 'X'),
+    ("-.--", 'Y'),
+    ("--..", 'Z'),
+];
+
+/// ITU digits and punctuation as (dit/dah pattern, character) pairs.
+pub(crate) const SYMBOLS: [(&str, char); 30] = [
+    ("-----", '0'),
+    (".----", '1'),
+    ("..---", '2'),
+    ("...--", '3'),
+    ("....-", '4'),
+    (".....", '5'),
+    ("-....", '6'),
+    ("--...", '7'),
+    ("---..", '8'),
+    ("----.", '9'),
+    (".-.-.-", '.'),
+    ("--..--", ','),
+    ("..--..", '?'),
+    (".----.", '\''),
+    ("-.-.--", '!'),
+    ("-..-.", '/'),
+    ("-.--.", '('),
+    ("-.--.-", ')'),
+    (".-...", '&'),
+    ("---...", ':'),
+    ("-.-.-.", ';'),
+    ("-...-", '='),
+    (".-.-.", '+'),
+    ("-....-", '-'),
+    ("..--.-", '_'),
+    (".-..-.", '"'),
+    ("...-..-", '$'),
+    (".--.-.", '@'),
+    ("..-.-", '¿'),
+    ("--...-", '¡'),
+];
+
+/// Prosigns as (dit/dah pattern, name) pairs. Prosigns are sent as one character
+/// without letter gaps and rendered as `<AR>`.
+const PROSIGNS: [(&str, &str); 9] = [
+    (".-.-.", "AR"),      // End of message, also '+'
+    ("-...-", "BT"),      // Break, also '='
+    ("-.--.", "KN"),      // Go ahead, named station only, also '('
+    (".-...", "AS"),      // Wait, also '&'
+    ("...-.-", "SK"),     // End of contact
+    ("-.-.-", "CT"),      // Start of message
+    ("...-.", "SN"),      // Understood
+    ("...---...", "SOS"), // Distress
+    ("........", "HH"),   // Error
+];
+
 impl Code {
-    pub fn display_code_string(code_string: Vec<Code>) -> String {
+    /// Shown in place of a dit/dah pattern that is not in the alphabet.
+    pub const UNKNOWN_CHARACTER: char = '#';
+
+    /// Render a code string as dots and dashes, with a space between letters
+    /// and a new line between words. American Morse long dashes are shown as `_` and `=`,
+    /// and spaces within characters as `^`.
+    pub fn display_code_string(code_string: &[Code]) -> String {
         code_string
+            .iter()
+            .map(|code| match code {
+                Code::Dit => '.',
+                Code::Dah => '-',
+                Code::LongDah => '_',
+                Code::ExtraLongDah => '=',
+                Code::Space => '^',
+                Code::Short => ' ',
+                Code::Long => '\n',
+            })
+            .collect()
+    }
+
+    /// Name of the error prosign, eight or more dits.
+    pub const ERROR_PROSIGN: &'static str = "HH";
+
+    /// The dits and dahs of a character as a string of `.` and `-`. American Morse long
+    /// dashes are written as `_` (L) and `=` (0), and spaces within characters as ` `.
+    pub fn pattern(character: &[Code]) -> String {
+        character
             .iter()
             .filter_map(|code| match code {
                 Code::Dit => Some('.'),
                 Code::Dah => Some('-'),
-                Code::Short => Some(' '),
-                Code::Long => Some('\n'),
+                Code::LongDah => Some('_'),
+                Code::ExtraLongDah => Some('='),
+                Code::Space => Some(' '),
+                Code::Short | Code::Long => None,
+            })
+            .collect()
+    }
+
+    /// Name of the prosign sent as a single character's dits and dahs, such as `AR`.
+    /// Any run of eight or more dits is the error prosign [`Code::ERROR_PROSIGN`].
+    pub fn translate_prosign(character: &[Code]) -> Option<&'static str> {
+        if character.len() >= 8 && character.iter().all(|code| *code == Code::Dit) {
+            return Some(Self::ERROR_PROSIGN);
+        }
+        let pattern = Self::pattern(character);
+        PROSIGNS
+            .iter()
+            .find_map(|(p, name)| (*p == pattern).then_some(*name))
+    }
+
+    /// The dits and dahs of a prosign by name, such as `AR`, ignoring case.
+    pub fn encode_prosign(name: &str) -> Option<Vec<Code>> {
+        PROSIGNS.iter().find_map(|(pattern, prosign)| {
+            prosign
+                .eq_ignore_ascii_case(name)
+                .then(|| Self::from_pattern(pattern))
+        })
+    }
+
+    /// Parse a pattern written like [`Code::pattern`].
+    pub fn from_pattern(pattern: &str) -> Vec<Code> {
+        pattern
+            .chars()
+            .map(|x| match x {
+                '.' => Code::Dit,
+                '_' => Code::LongDah,
+                '=' => Code::ExtraLongDah,
+                ' ' => Code::Space,
+                _ => Code::Dah,
             })
             .collect()
     }
 }
 
-#[derive(Serialize, Deserialize, Clone, Copy)]
+/// Duration thresholds (ms) used to classify marks and gaps.
+#[derive(Serialize, Deserialize, Clone)]
 pub struct DecoderSettings {
+    /// Marks shorter than this are dits, longer marks are dahs.
     pub dit_dah: u64,
+    /// Minimum gap between letters.
     pub letter: u64,
+    /// Minimum gap between words.
     pub letter_word: u64,
+    /// Continuously retune the thresholds with [`MorseDecoder::adapt`].
+    #[serde(default)]
+    pub adaptive: bool,
+    /// Marks and gaps shorter than this (ms) are merged into the surrounding gap or mark.
+    #[serde(default)]
+    pub glitch: u64,
+    /// Render AR, BT, KN and AS as prosigns rather than `+`, `=`, `(` and `&`.
+    /// Other prosigns are always recognized.
+    #[serde(default)]
+    pub prosigns: bool,
+    /// Delete the preceding word from the transcript when the error prosign is received,
+    /// instead of showing `<HH>`.
+    #[serde(default)]
+    pub error_deletes_word: bool,
+    /// Alphabet of the transcript. The decoder translates with the table set by
+    /// [`MorseDecoder::set_alphabet`], loaded from this with [`AlphabetSettings::load`].
+    #[serde(default)]
+    pub alphabet: AlphabetSettings,
+    /// Additional thresholds of the American Morse timing model.
+    #[serde(default)]
+    pub american: AmericanTiming,
+    /// Translate each letter into the most likely characters of the alphabet given the
+    /// mark and gap durations, see [`MorseDecoder::confidence`]. Not used for American Morse.
+    #[serde(default)]
+    pub probabilistic: bool,
+    /// Correct words that are not in the dictionary set by [`MorseDecoder::set_dictionary`],
+    /// see [`MorseDecoder::corrections`].
+    #[serde(default)]
+    pub correction: bool,
+    /// File with additional words for the dictionary, one per line, empty for only the
+    /// built-in words.
+    #[serde(default)]
+    pub word_list: String,
+}
+
+/// Duration thresholds (ms) of American Morse, which are used together with
+/// [`DecoderSettings::dit_dah`], [`DecoderSettings::letter`] and
+/// [`DecoderSettings::letter_word`] when the alphabet is [`AlphabetSettings::American`].
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+pub struct AmericanTiming {
+    /// Marks longer than this are long dashes (L) rather than dahs.
+    pub long_dah: u64,
+    /// Marks longer than this are the longer dash (0).
+    pub extra_long_dah: u64,
+    /// Minimum space within a character. Shorter gaps are between the dits and dahs,
+    /// longer gaps up to [`DecoderSettings::letter`] are spaces such as in C (`.. .`).
+    pub space: u64,
+}
+
+impl AmericanTiming {
+    /// Length of a dah in dits. The durations in dits of American Morse are shared by
+    /// [`crate::morse_encoder::Schedule::american`] and
+    /// [`DecoderSettings::retune_american`].
+    pub const DAH: f32 = 2.0;
+    /// Length of the long dash (L) in dits.
+    pub const LONG_DAH: f32 = 4.0;
+    /// Length of the longer dash (0) in dits.
+    pub const EXTRA_LONG_DAH: f32 = 6.0;
+    /// Length of a space within a character in dits. Other gaps within a character are
+    /// one dit.
+    pub const SPACE: f32 = 2.0;
+    /// Length of the gap between letters in dits.
+    pub const LETTER_GAP: f32 = 3.0;
+    /// Length of the gap between words in dits.
+    pub const WORD_GAP: f32 = 6.0;
+}
+
+impl Default for AmericanTiming {
+    fn default() -> Self {
+        Self {
+            long_dah: 600,
+            extra_long_dah: 1000,
+            space: 300,
+        }
+    }
 }
 
 impl Default for DecoderSettings {
@@ -36,109 +278,968 @@ impl Default for DecoderSettings {
             dit_dah: 300,
             letter: 500,
             letter_word: 2000,
+            adaptive: false,
+            glitch: 0,
+            prosigns: false,
+            error_deletes_word: false,
+            alphabet: AlphabetSettings::Latin,
+            american: AmericanTiming::default(),
+            probabilistic: false,
+            correction: false,
+            word_list: String::new(),
+        }
+    }
+}
+
+impl DecoderSettings {
+    /// Whether marks and gaps are classified with the American Morse timing model.
+    pub fn american(&self) -> bool {
+        self.alphabet == AlphabetSettings::American
+    }
+
+    /// Classify the duration (ms) of a mark.
+    pub fn classify_mark(&self, duration: u64) -> Code {
+        if duration < self.dit_dah {
+            Code::Dit // Short mark, dit
+        } else if !self.american() || duration < self.american.long_dah {
+            Code::Dah // Longer mark, dah
+        } else if duration < self.american.extra_long_dah {
+            Code::LongDah // American L
+        } else {
+            Code::ExtraLongDah // American 0
+        }
+    }
+
+    /// Classify the duration (ms) of a gap.
+    /// Returns [`None`] for the gap between the dits and dahs of a character.
+    pub fn classify_gap(&self, duration: u64) -> Option<Code> {
+        if duration < self.letter {
+            if self.american() && duration >= self.american.space {
+                Some(Code::Space) // American space within a character
+            } else {
+                None // Intra-character gap between dit and dah
+            }
+        } else if duration < self.letter_word {
+            Some(Code::Short) // Short gap between letters
+        } else {
+            Some(Code::Long) // Medium gap between words
         }
     }
+
+    /// Place each threshold halfway between the neighbouring estimated durations.
+    pub fn retune(&mut self, estimate: &TimingEstimate) {
+        self.dit_dah = ((estimate.dit + estimate.dah) / 2.0).round() as u64;
+        self.letter = ((estimate.element_gap + estimate.letter_gap) / 2.0).round() as u64;
+        self.letter_word = ((estimate.letter_gap + estimate.word_gap) / 2.0).round() as u64;
+    }
+
+    /// Place the American Morse thresholds halfway between the durations of
+    /// [`AmericanTiming`] for a dit of `unit` ms.
+    pub fn retune_american(&mut self, unit: f32) {
+        let threshold = |short: f32, long: f32| ((short + long) / 2.0 * unit).round() as u64;
+        self.dit_dah = threshold(1.0, AmericanTiming::DAH);
+        self.american.long_dah = threshold(AmericanTiming::DAH, AmericanTiming::LONG_DAH);
+        self.american.extra_long_dah =
+            threshold(AmericanTiming::LONG_DAH, AmericanTiming::EXTRA_LONG_DAH);
+        self.american.space = threshold(1.0, AmericanTiming::SPACE);
+        self.letter = threshold(AmericanTiming::SPACE, AmericanTiming::LETTER_GAP);
+        self.letter_word = threshold(AmericanTiming::LETTER_GAP, AmericanTiming::WORD_GAP);
+    }
 }
 
+/// Estimated mark and gap durations (ms).
+#[derive(Clone, Copy)]
+pub struct TimingEstimate {
+    /// Mean dit duration.
+    pub dit: f32,
+    /// Mean dah duration.
+    pub dah: f32,
+    /// Mean gap between the dits and dahs of a character.
+    pub element_gap: f32,
+    /// Mean gap between letters.
+    pub letter_gap: f32,
+    /// Mean gap between words.
+    pub word_gap: f32,
+}
+
+impl TimingEstimate {
+    /// The durations implied by the thresholds of `settings`, assuming each threshold lies
+    /// halfway between the standard 1, 3 and 7 units.
+    pub fn from_settings(settings: &DecoderSettings) -> Self {
+        let unit = settings.dit_dah as f32 / 2.0;
+        let gap_unit = settings.letter as f32 / 2.0;
+        Self {
+            dit: unit,
+            dah: 3.0 * unit,
+            element_gap: gap_unit,
+            letter_gap: 3.0 * gap_unit,
+            word_gap: settings.letter_word as f32 * 7.0 / 5.0,
+        }
+    }
+
+    /// Duration of one unit (ms) within characters, averaged over dits, dahs and element gaps.
+    pub fn unit(&self) -> f32 {
+        (self.dit + self.dah / 3.0 + self.element_gap) / 3.0
+    }
+}
+
+/// Sending speed (words per minute) using the PARIS standard word of 50 units.
+#[derive(Clone, Copy)]
+pub struct Speed {
+    /// Character speed, from the unit length within characters.
+    pub wpm: f32,
+    /// Effective speed including the actual letter and word gaps.
+    pub farnsworth_wpm: f32,
+}
+
+impl Speed {
+    /// PARIS contains 31 units of marks and element gaps, 4 letter gaps and 1 word gap.
+    pub fn from_estimate(estimate: &TimingEstimate) -> Self {
+        let unit = estimate.unit();
+        Self {
+            wpm: 60000.0 / (50.0 * unit),
+            farnsworth_wpm: 60000.0 / (31.0 * unit + 4.0 * estimate.letter_gap + estimate.word_gap),
+        }
+    }
+}
+
+impl std::fmt::Display for Speed {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{:.1} WPM ({:.1} WPM Farnsworth)",
+            self.wpm, self.farnsworth_wpm
+        )
+    }
+}
+
+/// One dimensional k-means clustering of `values`.
+/// `centers` holds the initial guesses and receives the results.
+/// A center without any values keeps its initial guess.
+fn cluster(values: &[f32], centers: &mut [f32]) {
+    for _ in 0..16 {
+        let mut sums = vec![(0.0, 0); centers.len()];
+        for value in values {
+            let nearest = centers
+                .iter()
+                .enumerate()
+                .min_by(|a, b| (a.1 - value).abs().total_cmp(&(b.1 - value).abs()))
+                .map(|(i, _)| i)
+                .unwrap();
+            sums[nearest].0 += value;
+            sums[nearest].1 += 1;
+        }
+        for (center, (sum, count)) in centers.iter_mut().zip(sums) {
+            if count > 0 {
+                *center = sum / count as f32;
+            }
+        }
+    }
+}
+
+/// Records the durations between on/off transitions of a signal and decodes them.
+///
+/// Recent durations are kept in a ring buffer of [`MorseDecoder::LENGTH`] entries, or the
+/// length given to [`MorseDecoder::with_history`], for statistics and display, while decoded
+/// characters are appended to an unbounded transcript.
 pub struct MorseDecoder {
-    ring: [(u64, bool); 128],
+    ring: Vec<(u64, bool)>,
     index: usize,
-    last_time: SystemTime,
+    /// Reference point for [`MorseDecoder::tick`].
+    epoch: Instant,
+    /// Time of the last transition, [`None`] until the first sample.
+    last_time: Option<Duration>,
+    /// Transition waiting to outlast [`DecoderSettings::glitch`].
+    pending: Option<(Duration, bool)>,
     last_on: bool,
+    /// Dits and dahs of the character currently being received.
+    character: Vec<Code>,
+    /// Durations (ms) of the marks of the current character and the gaps between them.
+    marks: Vec<u64>,
+    gaps: Vec<u64>,
+    transcript: String,
+    /// Confidence of each character of the transcript.
+    confidence: Vec<f32>,
+    word_ended: bool,
+    alphabet: Alphabet,
+    /// Whether the second table of the alphabet is selected.
+    shifted: bool,
+    /// Letters of the word currently being received.
+    letters: Vec<Vec<Code>>,
+    dictionary: Option<Dictionary>,
+    /// Each completed word of the transcript and its correction.
+    corrections: Vec<(String, String)>,
+    /// Length of the transcript up to the end of the last completed word.
+    corrected: usize,
 }
 
 impl MorseDecoder {
+    /// Number of transitions kept in the ring buffer.
     pub const LENGTH: usize = 128;
 
+    /// Standard deviation of the logarithm of mark and gap durations around their
+    /// expected length, for [`DecoderSettings::probabilistic`].
+    pub const SPREAD: f32 = 0.3;
+
+    /// Create a decoder with an empty history, starting in the off state.
     pub fn new() -> Self {
+        Self::with_history(Self::LENGTH)
+    }
+
+    /// Create a decoder that keeps the last `length` transitions for statistics instead of
+    /// [`MorseDecoder::LENGTH`], such as every transition of a recording.
+    pub fn with_history(length: usize) -> Self {
         Self {
-            ring: [(u64::MAX, false); 128],
+            ring: vec![(u64::MAX, false); length.max(1)],
             index: 0,
-            last_time: SystemTime::now(),
+            epoch: Instant::now(),
+            last_time: None,
+            pending: None,
             last_on: false,
+            character: Vec::new(),
+            marks: Vec::new(),
+            gaps: Vec::new(),
+            transcript: String::new(),
+            confidence: Vec::new(),
+            word_ended: true,
+            alphabet: Alphabet::latin(),
+            shifted: false,
+            letters: Vec::new(),
+            dictionary: None,
+            corrections: Vec::new(),
+            corrected: 0,
         }
     }
 
+    /// Translate the following characters with `alphabet`, starting with its main table.
+    pub fn set_alphabet(&mut self, alphabet: Alphabet) {
+        self.alphabet = alphabet;
+        self.shifted = false;
+    }
+
+    /// Alphabet the characters are translated with.
+    pub fn alphabet(&self) -> &Alphabet {
+        &self.alphabet
+    }
+
+    /// Correct the following words with `dictionary`, or stop correcting with [`None`].
+    pub fn set_dictionary(&mut self, dictionary: Option<Dictionary>) {
+        self.dictionary = dictionary;
+    }
+
+    /// Recorded mark (`on == false`) or gap (`on == true`) durations (ms).
+    pub fn durations(&self, on: bool) -> Vec<u64> {
+        self.ring
+            .iter()
+            .filter_map(|x| (x.0 != u64::MAX && x.1 == on).then_some(x.0))
+            .collect()
+    }
+
+    /// Estimate the element durations by clustering the recorded marks into
+    /// dits and dahs, and the recorded gaps into element, letter and word gaps.
+    /// Returns [`None`] until both dits and dahs have been seen.
+    pub fn estimate_timing(&self) -> Option<TimingEstimate> {
+        let marks: Vec<f32> = self.durations(false).iter().map(|x| *x as f32).collect();
+        let shortest = marks.iter().copied().reduce(f32::min)?;
+        let longest = marks.iter().copied().reduce(f32::max)?;
+        let mut mark_centers = [shortest, longest];
+        cluster(&marks, &mut mark_centers);
+        let [dit, dah] = mark_centers;
+        if dah < dit * 2.0 {
+            return None;
+        }
+
+        // Seed the gaps from their distribution, as Farnsworth spacing stretches the letter
+        // and word gaps by any factor. Most gaps are element gaps, most gaps longer than
+        // those are letter gaps, and most gaps clearly longer again are word gaps.
+        let unit = (dit + dah / 3.0) / 2.0;
+        let mut gaps: Vec<f32> = self.durations(true).iter().map(|x| *x as f32).collect();
+        gaps.sort_by(f32::total_cmp);
+        let element_gap = gaps.get(gaps.len() / 10).copied().unwrap_or(unit);
+        let longer = |gaps: &[f32], min: f32| -> Option<f32> {
+            let longer: Vec<f32> = gaps.iter().copied().filter(|x| *x >= min).collect();
+            (!longer.is_empty()).then(|| longer[longer.len() / 2])
+        };
+        let letter_gap = longer(&gaps, element_gap * 2.0).unwrap_or(element_gap * 3.0);
+        let word_gap = longer(&gaps, letter_gap * 1.5).unwrap_or(letter_gap * 7.0 / 3.0);
+
+        // Ignore idle periods between transmissions
+        gaps.retain(|x| *x < word_gap * 2.0);
+        let mut gap_centers = [element_gap, letter_gap, word_gap];
+        cluster(&gaps, &mut gap_centers);
+        let [element_gap, letter_gap, word_gap] = gap_centers;
+
+        Some(TimingEstimate {
+            dit,
+            dah,
+            element_gap,
+            letter_gap,
+            word_gap,
+        })
+    }
+
+    /// Estimate the dit duration (ms) of American Morse by clustering the recorded marks
+    /// into dits, dahs and long dashes. Returns [`None`] until dahs have been seen.
+    pub fn estimate_american_unit(&self) -> Option<f32> {
+        let marks: Vec<f32> = self.durations(false).iter().map(|x| *x as f32).collect();
+        let shortest = marks.iter().copied().reduce(f32::min)?;
+        let longest = marks.iter().copied().reduce(f32::max)?;
+        if longest < shortest * 1.5 {
+            return None;
+        }
+        let mut centers = [
+            shortest,
+            shortest * AmericanTiming::DAH,
+            shortest * AmericanTiming::LONG_DAH,
+            shortest * AmericanTiming::EXTRA_LONG_DAH,
+        ];
+        cluster(&marks, &mut centers);
+        Some(centers[0])
+    }
+
+    /// Retune the thresholds of `settings` from the recorded marks and gaps, with the
+    /// American Morse timing model if it is selected.
+    pub fn adapt(&self, settings: &mut DecoderSettings) {
+        if settings.american() {
+            if let Some(unit) = self.estimate_american_unit() {
+                settings.retune_american(unit);
+            }
+        } else if let Some(estimate) = self.estimate_timing() {
+            settings.retune(&estimate);
+        }
+    }
+
+    /// Estimate the sending speed from the recorded marks and gaps.
+    pub fn speed(&self) -> Option<Speed> {
+        self.estimate_timing()
+            .map(|estimate| Speed::from_estimate(&estimate))
+    }
+
+    /// Tabulate the recorded mark and gap durations, longest first.
     pub fn display(&self) -> String {
         let mut text = String::new();
-        let mut mark_times: Vec<u64> = self
-            .ring
-            .iter()
-            .filter_map(|x| (x.0 != u64::MAX && !x.1).then_some(x.0))
-            .collect();
+        let mut mark_times = self.durations(false);
         mark_times.sort();
         mark_times.reverse();
         let marks = mark_times.len();
-        let mut gap_times: Vec<u64> = self
-            .ring
-            .iter()
-            .filter_map(|x| (x.0 != u64::MAX && x.1).then_some(x.0))
-            .collect();
+        let mut gap_times = self.durations(true);
         gap_times.sort();
         gap_times.reverse();
         let gaps = gap_times.len();
 
         text += "Durations (ms)\nMarks Gaps\n----- -----";
-        for i in 0..marks.min(gaps) {
-            text += &format!("\n{:05} {:05}", mark_times[i], gap_times[i]);
+        for (mark_time, gap_time) in mark_times.iter().zip(&gap_times) {
+            text += &format!("\n{:05} {:05}", mark_time, gap_time);
         }
         if marks > gaps {
-            for i in gaps..marks {
-                text += &format!("\n{:05}", mark_times[i]);
+            for mark_time in &mark_times[gaps..] {
+                text += &format!("\n{:05}", mark_time);
             }
         } else {
-            for i in marks..gaps {
-                text += &format!("\n      {:05}", gap_times[i]);
+            for gap_time in &gap_times[marks..] {
+                text += &format!("\n      {:05}", gap_time);
             }
         }
         text
     }
 
-    pub fn tick(&mut self, on: bool) {
-        let now = SystemTime::now();
-        if self.last_on != on {
-            self.index = (self.index + 1) % Self::LENGTH;
-            self.ring[self.index] = (
-                now.duration_since(self.last_time).unwrap().as_millis() as u64,
-                on,
-            );
+    /// Sample the signal at the current time.
+    pub fn tick(&mut self, on: bool, settings: &DecoderSettings) {
+        self.sample(on, self.epoch.elapsed(), settings);
+    }
+
+    /// Sample the signal at `time`, measured from any fixed starting point
+    /// (e.g. an [`Instant`] taken at startup, or a sample counter converted to a [`Duration`]).
+    /// A time earlier than the last transition is treated as equal to it.
+    pub fn sample(&mut self, on: bool, time: Duration, settings: &DecoderSettings) {
+        let Some(last_time) = self.last_time else {
+            self.last_time = Some(time);
             self.last_on = on;
-            self.last_time = now;
+            return;
+        };
+        let now = time.max(last_time);
+
+        // Confirm the pending transition once it has lasted longer than a glitch, even if
+        // the signal has switched again since, or drop it if it returned sooner.
+        if let Some((pending_time, pending_on)) = self.pending {
+            if now.saturating_sub(pending_time).as_millis() as u64 >= settings.glitch {
+                self.pending = None;
+                self.transition(pending_on, pending_time, settings);
+            } else if on != pending_on {
+                self.pending = None;
+            }
         }
+
+        if self.pending.is_none() && self.last_on != on {
+            if settings.glitch == 0 {
+                self.transition(on, now, settings);
+            } else {
+                self.pending = Some((now, on));
+            }
+        }
+
+        if !self.last_on {
+            // Finish the character or word without waiting for the next mark.
+            let end = self.pending.map_or(now, |(pending_time, _)| pending_time);
+            let duration = end
+                .saturating_sub(self.last_time.unwrap_or(end))
+                .as_millis() as u64;
+            self.end_gap(duration, settings);
+        }
+    }
+
+    /// Record a confirmed transition to `on` at `time`.
+    fn transition(&mut self, on: bool, time: Duration, settings: &DecoderSettings) {
+        let last_time = self.last_time.unwrap_or(time);
+        let duration = (time.max(last_time) - last_time).as_millis() as u64;
+        self.index = (self.index + 1) % self.ring.len();
+        self.ring[self.index] = (duration, on);
+        self.last_on = on;
+        self.last_time = Some(time.max(last_time));
+        if on {
+            match settings.classify_gap(duration) {
+                _ if self.character.is_empty() => {}
+                Some(Code::Space) => self.character.push(Code::Space),
+                None => self.gaps.push(duration),
+                _ => {}
+            }
+            self.end_gap(duration, settings);
+            self.word_ended = false;
+        } else {
+            self.character.push(settings.classify_mark(duration));
+            self.marks.push(duration);
+        }
+    }
+
+    /// Append the current character and word gap to the transcript once the gap is long enough.
+    fn end_gap(&mut self, duration: u64, settings: &DecoderSettings) {
+        let gap = settings
+            .classify_gap(duration)
+            .filter(|gap| *gap != Code::Space);
+        if gap.is_some() && !self.character.is_empty() {
+            let characters = if settings.probabilistic && !settings.american() {
+                self.most_likely(settings)
+            } else {
+                vec![(self.character.clone(), 1.0)]
+            };
+            for (character, confidence) in characters {
+                self.push_character(&character, confidence, settings);
+            }
+            self.character.clear();
+            self.marks.clear();
+            self.gaps.clear();
+        }
+        if gap == Some(Code::Long) && !self.word_ended {
+            self.correct_word(settings);
+            if !self.transcript.is_empty() {
+                self.push_text(" ", 1.0);
+            }
+            self.corrected = self.transcript.len();
+            self.word_ended = true;
+        }
+    }
+
+    /// Translate a character into the transcript, or act on it if it is the error prosign
+    /// or switches the alphabet's tables.
+    fn push_character(&mut self, character: &[Code], confidence: f32, settings: &DecoderSettings) {
+        if settings.error_deletes_word
+            && Code::translate_prosign(character) == Some(Code::ERROR_PROSIGN)
+        {
+            self.delete_word();
+        } else if let Some(shifted) = self.alphabet.switch(character, self.shifted) {
+            self.shifted = shifted;
+        } else {
+            let text = self
+                .alphabet
+                .translate(character, self.shifted, settings.prosigns);
+            self.push_text(&text, confidence);
+            self.letters.push(character.to_vec());
+        }
+    }
+
+    /// Record the word that just ended and its correction with the dictionary, if any.
+    fn correct_word(&mut self, settings: &DecoderSettings) {
+        let letters = std::mem::take(&mut self.letters);
+        let word = self.transcript[self.corrected..].trim_start();
+        if word.is_empty() {
+            return;
+        }
+        let correction = self.dictionary.as_ref().and_then(|dictionary| {
+            dictionary.correct(&letters, |letter| {
+                self.alphabet
+                    .translate(letter, self.shifted, settings.prosigns)
+            })
+        });
+        let word = word.to_owned();
+        self.corrections
+            .push((word.clone(), correction.unwrap_or(word)));
+    }
+
+    /// Append `text` to the transcript with the same confidence for each character.
+    fn push_text(&mut self, text: &str, confidence: f32) {
+        self.transcript.push_str(text);
+        self.confidence
+            .extend(std::iter::repeat_n(confidence, text.chars().count()));
+    }
+
+    /// Find the most likely characters sent as the marks of the current character, which
+    /// may be several letters if some gaps were letter gaps, with the confidence of each.
+    ///
+    /// Durations are log-normally distributed around the estimated dit and dah, element gap
+    /// and letter gap. Every split of the marks into letters is scored with the best
+    /// pattern of the alphabet for each letter, and the best split is found by dynamic
+    /// programming (Viterbi). The confidence is the probability of the chosen pattern among
+    /// the alphabet's patterns of the same length, times that of its gaps being element gaps.
+    fn most_likely(&self, settings: &DecoderSettings) -> Vec<(Vec<Code>, f32)> {
+        let timing = self
+            .estimate_timing()
+            .unwrap_or_else(|| TimingEstimate::from_settings(settings));
+        let log_likelihood = |duration: u64, expected: f32| {
+            let error = (duration.max(1) as f32).ln() - expected.max(1.0).ln();
+            -error * error / (2.0 * Self::SPREAD * Self::SPREAD)
+        };
+        // Log-likelihood of a gap as an element gap, and the probability it is one
+        // rather than a letter gap.
+        let element_gap = |gap: u64| {
+            let element = log_likelihood(gap, timing.element_gap);
+            let letter = log_likelihood(gap, timing.letter_gap);
+            (element, 1.0 / (1.0 + (letter - element).exp()))
+        };
+
+        let mut patterns: BTreeSet<&str> = self.alphabet.patterns(self.shifted).collect();
+        patterns.extend(PROSIGNS.iter().map(|(pattern, _)| *pattern));
+        let candidates: Vec<Vec<Code>> = patterns.into_iter().map(Code::from_pattern).collect();
+        // Letters are at most as long as the longest pattern, except for longer runs of dits,
+        // which are the error prosign too. They are scored as one case that extends the
+        // error prosign ending at the previous mark by a dit.
+        let longest = candidates.iter().map(Vec::len).max().unwrap_or(0);
+        let error = candidates
+            .iter()
+            .position(|pattern| Code::translate_prosign(pattern) == Some(Code::ERROR_PROSIGN));
+
+        // best[i]: score of the best split of the first i marks, where the last letter
+        // starts, its pattern and confidence.
+        let count = self.marks.len();
+        let mut best: Vec<Option<(f32, usize, usize, f32)>> = vec![None; count + 1];
+        best[0] = Some((0.0, 0, 0, 1.0));
+        // errors[i]: score of the best split of the first i marks that ends with the error
+        // prosign, where it starts and its confidence.
+        let mut errors: Vec<Option<(f32, usize, f32)>> = vec![None; count + 1];
+        for end in 1..=count {
+            for start in end.saturating_sub(longest)..end {
+                let Some((previous, ..)) = best[start] else {
+                    continue;
+                };
+                let marks = &self.marks[start..end];
+                let scores: Vec<(f32, usize)> = candidates
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, pattern)| pattern.len() == marks.len())
+                    .map(|(i, pattern)| {
+                        let score = marks
+                            .iter()
+                            .zip(pattern)
+                            .map(|(mark, code)| match code {
+                                Code::Dit => log_likelihood(*mark, timing.dit),
+                                _ => log_likelihood(*mark, timing.dah),
+                            })
+                            .sum();
+                        (score, i)
+                    })
+                    .collect();
+                let Some(&(score, pattern)) = scores.iter().max_by(|a, b| a.0.total_cmp(&b.0))
+                else {
+                    continue;
+                };
+                let mut total = previous;
+                let mut probability = 1.0;
+                if start > 0 {
+                    total += log_likelihood(self.gaps[start - 1], timing.letter_gap);
+                }
+                for gap in &self.gaps[start..end - 1] {
+                    let (score, element) = element_gap(*gap);
+                    total += score;
+                    probability *= element;
+                }
+                let confidence = |score: f32| {
+                    probability / scores.iter().map(|x| (x.0 - score).exp()).sum::<f32>()
+                };
+                if best[end].is_none_or(|(best, ..)| total + score > best) {
+                    best[end] = Some((total + score, start, pattern, confidence(score)));
+                }
+                if let Some(&(score, _)) = scores.iter().find(|(_, i)| Some(*i) == error) {
+                    errors[end] = Some((total + score, start, confidence(score)));
+                }
+            }
+
+            if end >= 2 {
+                if let Some((total, start, confidence)) = errors[end - 1] {
+                    let (gap, element) = element_gap(self.gaps[end - 2]);
+                    let total = total + gap + log_likelihood(self.marks[end - 1], timing.dit);
+                    if errors[end].is_none_or(|(best, ..)| total > best) {
+                        errors[end] = Some((total, start, confidence * element));
+                    }
+                }
+            }
+            if let (Some(error), Some((total, start, confidence))) = (error, errors[end]) {
+                if best[end].is_none_or(|(best, ..)| total > best) {
+                    best[end] = Some((total, start, error, confidence));
+                }
+            }
+        }
+
+        // Follow the best split back from the end
+        let mut characters = Vec::new();
+        let mut end = count;
+        while end > 0 {
+            let Some((_, start, pattern, confidence)) = best[end] else {
+                // No pattern of the alphabet has this many marks
+                return vec![(self.character.clone(), 0.0)];
+            };
+            let character = if Some(pattern) == error {
+                vec![Code::Dit; end - start]
+            } else {
+                candidates[pattern].clone()
+            };
+            characters.push((character, confidence));
+            end = start;
+        }
+        characters.reverse();
+        characters
+    }
+
+    /// Remove the last word from the transcript, keeping the space before it.
+    fn delete_word(&mut self) {
+        let end = self.transcript.trim_end().len();
+        let start = self.transcript[..end].rfind(' ').map_or(0, |i| i + 1);
+        self.transcript.truncate(start);
+        self.confidence.truncate(self.transcript.chars().count());
+        if self.corrected > start {
+            self.corrections.pop();
+            self.corrected = start;
+        }
+        self.letters.clear();
+        self.word_ended = true;
+    }
+
+    /// End a finite recording at `time`, completing the last character and word as if
+    /// the signal stayed off.
+    pub fn finish(&mut self, time: Duration, settings: &DecoderSettings) {
+        self.sample(false, time, settings);
+        let end = time + Duration::from_millis(settings.letter_word + settings.glitch + 1);
+        self.sample(false, end, settings);
+    }
+
+    /// All characters decoded since the last reset.
+    /// Characters are only appended once the following letter or word gap has been seen.
+    /// With [`DecoderSettings::error_deletes_word`], the error prosign removes the last word.
+    pub fn transcript(&self) -> &str {
+        &self.transcript
+    }
+
+    /// Each completed word of the transcript and its correction, which is the same word
+    /// unless [`MorseDecoder::set_dictionary`] found a better one.
+    pub fn corrections(&self) -> &[(String, String)] {
+        &self.corrections
+    }
+
+    /// The end of the transcript after the last completed word.
+    pub fn uncorrected(&self) -> &str {
+        &self.transcript[self.corrected..]
+    }
+
+    /// The transcript with each completed word replaced by its correction.
+    pub fn corrected_transcript(&self) -> String {
+        let mut text = String::new();
+        for (_, correction) in &self.corrections {
+            text += correction;
+            text.push(' ');
+        }
+        text + self.uncorrected().trim_start()
+    }
+
+    /// Confidence (0-1) of each character of the transcript. With
+    /// [`DecoderSettings::probabilistic`], it is the probability of the decoded letter given
+    /// the mark and gap durations, otherwise 1.
+    pub fn confidence(&self) -> &[f32] {
+        &self.confidence
     }
 
+    /// Clear the recorded durations and the transcript.
     pub fn reset(&mut self) {
         self.ring.fill((u64::MAX, false));
         self.index = 0;
-        self.last_time = SystemTime::now();
+        self.last_time = None;
+        self.pending = None;
         self.last_on = false;
+        self.character.clear();
+        self.marks.clear();
+        self.gaps.clear();
+        self.transcript.clear();
+        self.confidence.clear();
+        self.letters.clear();
+        self.corrections.clear();
+        self.corrected = 0;
+        self.word_ended = true;
+        self.shifted = false;
     }
 
+    /// Classify the durations in the ring buffer, oldest first.
     pub fn decode(&self, settings: &DecoderSettings) -> Vec<Code> {
         let mut code: Vec<Code> = Vec::new();
-        for i in (((self.index + 1) % Self::LENGTH)..Self::LENGTH).chain(0..=self.index) {
+        let length = self.ring.len();
+        for i in (((self.index + 1) % length)..length).chain(0..=self.index) {
             let (duration, on) = self.ring[i];
             if duration == u64::MAX {
                 continue;
             }
             if on {
                 // Signal was switched on, duration is the preceding off period.
-                if duration < settings.letter {
-                    // Intra-character gap between dit and dah
-                } else if duration < settings.letter_word {
-                    code.push(Code::Short) // Short gap between letters
-                } else {
-                    code.push(Code::Long) // Medium gap between words
-                }
+                code.extend(settings.classify_gap(duration));
             } else {
                 // Signal was switched off, duration is the preceding on period.
-                if duration < settings.dit_dah {
-                    code.push(Code::Dit) // Short mark, dit
-                } else {
-                    code.push(Code::Dah) // Longer mark, dah
-                }
+                code.push(settings.classify_mark(duration));
             }
         }
         code
     }
 }
+
+impl Default for MorseDecoder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::morse_encoder::{EncoderSettings, Schedule};
+
+    /// Decode `schedule` by feeding only its transitions, followed by a sample long after
+    /// the end to finish the last word.
+    fn decode(schedule: &Schedule, settings: &DecoderSettings) -> String {
+        let mut decoder = MorseDecoder::new();
+        decoder.set_alphabet(settings.alphabet.load().unwrap());
+        for (time, on) in schedule.transitions() {
+            decoder.sample(on, time, settings);
+        }
+        decoder.sample(
+            false,
+            schedule.duration() + Duration::from_secs(10),
+            settings,
+        );
+        decoder.transcript().trim().to_owned()
+    }
+
+    /// Decode `text` sent at `wpm` in ITU Latin.
+    fn round_trip(text: &str, wpm: f32, settings: &DecoderSettings) -> String {
+        let encoder = EncoderSettings {
+            wpm,
+            farnsworth_wpm: wpm,
+        };
+        decode(
+            &Schedule::from_text(text, &Alphabet::latin(), &encoder),
+            settings,
+        )
+    }
+
+    /// Thresholds halfway between the standard durations at `wpm`.
+    fn settings(wpm: f32) -> DecoderSettings {
+        let unit = 1200.0 / wpm;
+        let mut settings = DecoderSettings::default();
+        settings.retune(&TimingEstimate {
+            dit: unit,
+            dah: 3.0 * unit,
+            element_gap: unit,
+            letter_gap: 3.0 * unit,
+            word_gap: 7.0 * unit,
+        });
+        settings
+    }
+
+    #[test]
+    fn itu_round_trip() {
+        for wpm in [5.0, 20.0, 40.0] {
+            let text = "CQ DE W1AW 73, 599? 1/2 = 0.5";
+            assert_eq!(round_trip(text, wpm, &settings(wpm)), text);
+        }
+        // Patterns that are not in the alphabet are shown as a placeholder
+        let mut decoder = MorseDecoder::new();
+        let settings = settings(20.0);
+        let mut time = 0;
+        for mark in [60, 60, 180, 180] {
+            decoder.sample(true, Duration::from_millis(time), &settings);
+            decoder.sample(false, Duration::from_millis(time + mark), &settings);
+            time += mark + 60;
+        }
+        decoder.sample(false, Duration::from_millis(time + 5000), &settings);
+        assert_eq!(decoder.transcript().trim(), "#");
+    }
+
+    #[test]
+    fn wabun_round_trip() {
+        let settings = DecoderSettings {
+            alphabet: AlphabetSettings::Wabun,
+            ..settings(20.0)
+        };
+        let alphabet = settings.alphabet.load().unwrap();
+        let encoder = EncoderSettings::default();
+        let text = "DE JA1ABC イロハ ニホヘト K";
+        let schedule = Schedule::from_text(text, &alphabet, &encoder);
+        assert_eq!(decode(&schedule, &settings), text);
+
+        // SN only switches back from Wabun, in Latin it is a prosign like any other
+        let schedule = Schedule::from_text("CQ <SN> K", &alphabet, &encoder);
+        assert_eq!(decode(&schedule, &settings), "CQ <SN> K");
+    }
+
+    #[test]
+    fn glitch_filter_keeps_transitions() {
+        for glitch in [5, 10, 30] {
+            let settings = DecoderSettings {
+                glitch,
+                ..settings(30.0)
+            };
+            assert_eq!(round_trip("PARIS PARIS", 30.0, &settings), "PARIS PARIS");
+        }
+    }
+
+    #[test]
+    fn glitch_filter_drops_glitches() {
+        let settings = DecoderSettings {
+            glitch: 10,
+            ..settings(20.0)
+        };
+        let mut decoder = MorseDecoder::new();
+        let ms = Duration::from_millis;
+        // A dah (E is a dit) interrupted by a 5 ms dropout, then a 3 ms spike in the gap
+        let samples = [
+            (0, false),
+            (100, true),
+            (200, false),
+            (205, true),
+            (280, false),
+            (400, true),
+            (403, false),
+        ];
+        for (time, on) in samples {
+            decoder.sample(on, ms(time), &settings);
+        }
+        decoder.sample(false, ms(5000), &settings);
+        assert_eq!(decoder.transcript().trim(), "T");
+    }
+
+    #[test]
+    fn american_round_trip() {
+        let encoder = EncoderSettings::default();
+        let alphabet = AlphabetSettings::American;
+        let code = crate::morse_encoder::encode("CAT LO0 CORNELL 10", &alphabet.load().unwrap());
+        let mut settings = DecoderSettings {
+            alphabet,
+            ..DecoderSettings::default()
+        };
+        settings.retune_american(encoder.unit());
+        let schedule = Schedule::american(&code, &encoder);
+        assert_eq!(decode(&schedule, &settings), "CAT LO0 CORNELL 10");
+    }
+
+    #[test]
+    fn farnsworth_speed() {
+        for farnsworth_wpm in [5.0, 10.0, 12.0, 15.0, 20.0] {
+            let encoder = EncoderSettings {
+                wpm: 20.0,
+                farnsworth_wpm,
+            };
+            let text = "CQ CQ DE W1AW W1AW K THE QUICK BROWN FOX";
+            let schedule = Schedule::from_text(text, &Alphabet::latin(), &encoder);
+            let mut decoder = MorseDecoder::new();
+            let settings = DecoderSettings::default();
+            for (time, on) in schedule.transitions() {
+                decoder.sample(on, time, &settings);
+            }
+            let speed = decoder.speed().unwrap();
+            assert!((speed.wpm - 20.0).abs() < 0.5, "{speed}");
+            assert!(
+                (speed.farnsworth_wpm - farnsworth_wpm).abs() < farnsworth_wpm * 0.05,
+                "{speed} instead of {farnsworth_wpm}"
+            );
+
+            // The retuned thresholds decode the same text
+            let mut settings = DecoderSettings::default();
+            decoder.adapt(&mut settings);
+            assert_eq!(decode(&schedule, &settings), text);
+        }
+    }
+
+    #[test]
+    fn long_runs_of_marks() {
+        let settings = DecoderSettings {
+            probabilistic: true,
+            ..settings(20.0)
+        };
+        let ms = Duration::from_millis;
+        let decode = |marks: &[u64]| {
+            let mut decoder = MorseDecoder::new();
+            let mut time = 0;
+            for mark in marks {
+                decoder.sample(false, ms(time), &settings);
+                decoder.sample(true, ms(time + 60), &settings);
+                time += 60 + mark;
+            }
+            decoder.sample(false, ms(time), &settings);
+            decoder.sample(false, ms(time + 5000), &settings);
+            decoder
+        };
+
+        // Any number of dits is a single error prosign
+        let decoder = decode(&[60; 3000]);
+        assert_eq!(decoder.transcript().trim(), "<HH>");
+
+        // A flickering signal is split into letters no longer than the alphabet's
+        let marks: Vec<u64> = (0..3000).map(|i| [60, 180, 180][i % 3]).collect();
+        let decoder = decode(&marks);
+        let transcript = decoder.transcript().trim();
+        assert!(transcript.chars().count() >= 3000 / 8, "{transcript}");
+    }
+
+    #[test]
+    fn prosign_round_trip() {
+        let text = "QRV <AR> <SK> <KN> <BT>";
+        let mut settings = settings(20.0);
+        assert_eq!(round_trip(text, 20.0, &settings), "QRV + <SK> ( =");
+        settings.prosigns = true;
+        assert_eq!(round_trip(text, 20.0, &settings), text);
+    }
+
+    #[test]
+    fn error_deletes_word() {
+        let mut settings = settings(20.0);
+        let text = "THE QUIK <HH> QUICK FOX";
+        assert_eq!(round_trip(text, 20.0, &settings), text);
+        settings.error_deletes_word = true;
+        assert_eq!(round_trip(text, 20.0, &settings), "THE QUICK FOX");
+    }
+
+    #[test]
+    fn error_prosign_confidence() {
+        let settings = DecoderSettings {
+            probabilistic: true,
+            ..settings(20.0)
+        };
+        let encoder = EncoderSettings::default();
+        let schedule = Schedule::from_text("TEST <HH> TEST", &Alphabet::latin(), &encoder);
+        let mut decoder = MorseDecoder::new();
+        for (time, on) in schedule.transitions() {
+            decoder.sample(on, time, &settings);
+        }
+        decoder.sample(
+            false,
+            schedule.duration() + Duration::from_secs(10),
+            &settings,
+        );
+        assert_eq!(decoder.transcript().trim(), "TEST <HH> TEST");
+        let confidence = decoder.confidence();
+        assert!(confidence.iter().all(|x| *x > 0.9), "{confidence:?}");
+    }
+}
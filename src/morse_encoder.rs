@@ -0,0 +1,273 @@
+//! Encoding of text into Morse code and timed on/off signals.
+
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    alphabet::Alphabet,
+    morse_decoder::{AmericanTiming, Code},
+};
+
+/// Translate text into dits and dahs with `alphabet`, with [`Code::Short`] between letters
+/// and [`Code::Long`] between words. Letters are matched ignoring case. Prosigns are
+/// written like `<AR>`. Characters of the second table of an alphabet such as Wabun are
+/// preceded by its switching pattern, and the text switches back to the main table at
+/// the end. Characters that are not in the alphabet and unknown prosigns are skipped.
+pub fn encode(text: &str, alphabet: &Alphabet) -> Vec<Code> {
+    let mut code = Vec::new();
+    let mut shifted = false;
+    let push = |code: &mut Vec<Code>, letters: Vec<Vec<Code>>, gap: Code| {
+        for (i, letter) in letters.into_iter().enumerate() {
+            if i > 0 || !code.is_empty() {
+                code.push(if i == 0 { gap } else { Code::Short });
+            }
+            code.extend(letter);
+        }
+    };
+    for word in text.split_whitespace() {
+        let letters = letters(word, alphabet, &mut shifted);
+        push(&mut code, letters, Code::Long);
+    }
+    if shifted {
+        if let Some(shift) = &alphabet.shift {
+            push(
+                &mut code,
+                vec![Code::from_pattern(&shift.leave)],
+                Code::Short,
+            );
+        }
+    }
+    code
+}
+
+/// The dits and dahs of each character and prosign of a word, with the patterns that
+/// switch tables where needed.
+fn letters(word: &str, alphabet: &Alphabet, shifted: &mut bool) -> Vec<Vec<Code>> {
+    let mut letters = Vec::new();
+    let mut chars = word.chars();
+    while let Some(character) = chars.next() {
+        if character == '<' {
+            let name: String = chars.by_ref().take_while(|c| *c != '>').collect();
+            letters.extend(Code::encode_prosign(&name));
+            continue;
+        }
+        let text = character.to_string();
+        let upper = character.to_uppercase().to_string();
+        let find = |shifted| {
+            alphabet
+                .find(&upper, shifted)
+                .or_else(|| alphabet.find(&text, shifted))
+        };
+        if let Some(pattern) = find(*shifted) {
+            letters.push(Code::from_pattern(pattern));
+        } else if let (Some(shift), Some(pattern)) = (&alphabet.shift, find(!*shifted)) {
+            let switch = if *shifted { &shift.leave } else { &shift.enter };
+            letters.push(Code::from_pattern(switch));
+            letters.push(Code::from_pattern(pattern));
+            *shifted = !*shifted;
+        }
+    }
+    letters
+}
+
+/// Sending speed of a generated signal.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+pub struct EncoderSettings {
+    /// Character speed in words per minute (PARIS), which sets the length of the
+    /// dits, dahs and the gaps within a character.
+    pub wpm: f32,
+    /// Effective speed in words per minute with Farnsworth spacing. Below
+    /// [`EncoderSettings::wpm`], the letter and word gaps are stretched to slow the text
+    /// down while the characters keep their speed.
+    pub farnsworth_wpm: f32,
+}
+
+impl Default for EncoderSettings {
+    fn default() -> Self {
+        Self {
+            wpm: 20.0,
+            farnsworth_wpm: 20.0,
+        }
+    }
+}
+
+impl EncoderSettings {
+    /// Length of a dit (ms).
+    pub fn unit(&self) -> f32 {
+        1200.0 / self.wpm.max(1.0)
+    }
+
+    /// Length of the gaps between letters and between words (ms).
+    /// PARIS takes 31 units of marks and element gaps, 4 letter gaps and a word gap,
+    /// with letter and word gaps in the standard 3:7 ratio.
+    pub fn gaps(&self) -> (f32, f32) {
+        let unit = self.unit();
+        let farnsworth = self.farnsworth_wpm.clamp(1.0, self.wpm.max(1.0));
+        let gap_unit = ((60000.0 / farnsworth - 31.0 * unit) / 19.0).max(unit);
+        (3.0 * gap_unit, 7.0 * gap_unit)
+    }
+}
+
+/// A signal as the successive on and off states and how long each is held.
+#[derive(Clone, Default, Debug)]
+pub struct Schedule {
+    /// State and duration of each mark and gap, starting with the first mark.
+    pub elements: Vec<(bool, Duration)>,
+}
+
+impl Schedule {
+    /// Time the code at the speed of `settings`.
+    pub fn new(code: &[Code], settings: &EncoderSettings) -> Self {
+        let (letter_gap, word_gap) = settings.gaps();
+        Self::timed(code, settings.unit(), 3.0, letter_gap, word_gap)
+    }
+
+    /// Time American Morse code at the speed of `settings` with the durations of
+    /// [`AmericanTiming`]. Farnsworth spacing stretches the letter and word gaps by the
+    /// same factor as in [`Schedule::new`].
+    pub fn american(code: &[Code], settings: &EncoderSettings) -> Self {
+        let unit = settings.unit();
+        let stretch = settings.gaps().0 / (3.0 * unit);
+        Self::timed(
+            code,
+            unit,
+            AmericanTiming::DAH,
+            AmericanTiming::LETTER_GAP * unit * stretch,
+            AmericanTiming::WORD_GAP * unit * stretch,
+        )
+    }
+
+    /// Time the code with a dit of `unit` ms, a dah of `dah` dits, and letter and word
+    /// gaps (ms). Long dashes and spaces within characters follow [`AmericanTiming`].
+    fn timed(code: &[Code], unit: f32, dah: f32, letter_gap: f32, word_gap: f32) -> Self {
+        let ms = |x: f32| Duration::from_secs_f32(x / 1000.0);
+        let mut elements: Vec<(bool, Duration)> = Vec::new();
+        for code in code {
+            let mark = match code {
+                Code::Dit => unit,
+                Code::Dah => dah * unit,
+                Code::LongDah => AmericanTiming::LONG_DAH * unit,
+                Code::ExtraLongDah => AmericanTiming::EXTRA_LONG_DAH * unit,
+                Code::Space => {
+                    elements.push((false, ms(AmericanTiming::SPACE * unit)));
+                    continue;
+                }
+                Code::Short => {
+                    elements.push((false, ms(letter_gap)));
+                    continue;
+                }
+                Code::Long => {
+                    elements.push((false, ms(word_gap)));
+                    continue;
+                }
+            };
+            if elements.last().is_some_and(|(on, _)| *on) {
+                elements.push((false, ms(unit)));
+            }
+            elements.push((true, ms(mark)));
+        }
+        Self { elements }
+    }
+
+    /// Encode `text` with `alphabet` and time it.
+    pub fn from_text(text: &str, alphabet: &Alphabet, settings: &EncoderSettings) -> Self {
+        Self::new(&encode(text, alphabet), settings)
+    }
+
+    /// Total length of the signal.
+    pub fn duration(&self) -> Duration {
+        self.elements.iter().map(|(_, duration)| *duration).sum()
+    }
+
+    /// Whether the signal is on at `time` from its start. Off before and after.
+    pub fn is_on(&self, time: Duration) -> bool {
+        let mut start = Duration::ZERO;
+        for (on, duration) in &self.elements {
+            start += *duration;
+            if time < start {
+                return *on;
+            }
+        }
+        false
+    }
+
+    /// The time each mark and gap starts, followed by the final switch off,
+    /// as samples for [`crate::morse_decoder::MorseDecoder::sample`].
+    pub fn transitions(&self) -> Vec<(Duration, bool)> {
+        let mut time = Duration::ZERO;
+        let mut transitions = Vec::with_capacity(self.elements.len() + 1);
+        for (on, duration) in &self.elements {
+            transitions.push((time, *on));
+            time += *duration;
+        }
+        transitions.push((time, false));
+        transitions
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Encode `text` and render it with [`Code::display_code_string`].
+    fn encoded(text: &str, alphabet: &Alphabet) -> String {
+        Code::display_code_string(&encode(text, alphabet))
+    }
+
+    #[test]
+    fn encodes_prosigns() {
+        let latin = Alphabet::latin();
+        assert_eq!(encoded("k <ar>", &latin), "-.-\n.-.-.");
+        assert_eq!(encoded("E<SK>E", &latin), ". ...-.- .");
+        // Unknown prosigns are skipped, an unterminated one ends with the word
+        assert_eq!(encoded("E <XYZ> <SK", &latin), ".\n...-.-");
+    }
+
+    #[test]
+    fn skips_unknown_characters() {
+        let latin = Alphabet::latin();
+        assert_eq!(encoded("A~B", &latin), ".- -...");
+        assert_eq!(encoded("~ A", &latin), ".-");
+        assert_eq!(encoded("~", &latin), "");
+    }
+
+    #[test]
+    fn switches_tables() {
+        let wabun = crate::alphabet::AlphabetSettings::Wabun.load().unwrap();
+        // DO before the katakana, SN before the next Latin letter
+        assert_eq!(encoded("A イ B", &wabun), ".-\n-..--- .-\n...-. -...");
+        // The text switches back to Latin at the end
+        assert_eq!(encoded("イロ", &wabun), "-..--- .- .-.- ...-.");
+    }
+
+    #[test]
+    fn farnsworth_gaps() {
+        let settings = EncoderSettings::default();
+        assert_eq!(settings.unit(), 60.0);
+        assert_eq!(settings.gaps(), (180.0, 420.0));
+
+        // PARIS and a word gap take a minute divided by the effective speed
+        for (wpm, farnsworth_wpm) in [(20.0, 20.0), (20.0, 10.0), (25.0, 5.0), (15.0, 12.0)] {
+            let settings = EncoderSettings {
+                wpm,
+                farnsworth_wpm,
+            };
+            let paris = Schedule::from_text("PARIS", &Alphabet::latin(), &settings);
+            let (letter_gap, word_gap) = settings.gaps();
+            let duration = paris.duration().as_secs_f32() * 1000.0 + word_gap;
+            assert!(
+                (duration - 60000.0 / farnsworth_wpm).abs() < 1.0,
+                "{duration} ms at {wpm}/{farnsworth_wpm} WPM"
+            );
+            assert!((word_gap / letter_gap - 7.0 / 3.0).abs() < 1e-4);
+        }
+
+        // Farnsworth spacing never speeds the gaps up
+        let settings = EncoderSettings {
+            wpm: 20.0,
+            farnsworth_wpm: 30.0,
+        };
+        assert_eq!(settings.gaps(), (180.0, 420.0));
+    }
+}
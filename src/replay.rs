@@ -0,0 +1,237 @@
+use std::{
+    fs::File,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use morse::sensor::{rgba_pixels, SensorSettings};
+
+/// A directory of numbered PNG frames and the time of each frame.
+pub struct Frames {
+    pub paths: Vec<PathBuf>,
+    pub times: Vec<Duration>,
+}
+
+impl Frames {
+    /// Find the PNG files in `directory`, ordered by the number in their names.
+    /// Frames are timed by `fps`, or by `timestamps`, a file with the time of each
+    /// frame in seconds on its own line. Timestamps are measured from the first, so they
+    /// may be absolute times such as Unix seconds.
+    pub fn open(directory: &Path, fps: f32, timestamps: Option<&Path>) -> Result<Self, String> {
+        let entries = std::fs::read_dir(directory)
+            .map_err(|error| format!("Unable to read {}: {error}.", directory.display()))?;
+        let mut frames: Vec<(u64, PathBuf)> = Vec::new();
+        for entry in entries {
+            let path = entry
+                .map_err(|error| format!("Unable to read {}: {error}.", directory.display()))?
+                .path();
+            if !path
+                .extension()
+                .is_some_and(|extension| extension.eq_ignore_ascii_case("png"))
+            {
+                continue;
+            }
+            let digits: String = path
+                .file_stem()
+                .map(|stem| stem.to_string_lossy())
+                .unwrap_or_default()
+                .chars()
+                .filter(char::is_ascii_digit)
+                .collect();
+            match digits.parse() {
+                Ok(number) => frames.push((number, path)),
+                Err(_) => eprintln!("[Replay] Skipping unnumbered frame {}.", path.display()),
+            }
+        }
+        if frames.is_empty() {
+            return Err(format!(
+                "No numbered PNG frames in {}.",
+                directory.display()
+            ));
+        }
+        frames.sort();
+        let paths: Vec<PathBuf> = frames.into_iter().map(|(_, path)| path).collect();
+
+        let times = match timestamps {
+            Some(file) => {
+                let text = std::fs::read_to_string(file)
+                    .map_err(|error| format!("Unable to read {}: {error}.", file.display()))?;
+                let times = text
+                    .lines()
+                    .map(str::trim)
+                    .filter(|line| !line.is_empty())
+                    .map(|line| {
+                        line.parse::<f64>()
+                            .ok()
+                            .filter(|seconds| *seconds >= 0.0)
+                            .map(Duration::from_secs_f64)
+                            .ok_or_else(|| {
+                                format!("Invalid timestamp \"{line}\" in {}.", file.display())
+                            })
+                    })
+                    .collect::<Result<Vec<_>, _>>()?;
+                if times.len() < paths.len() {
+                    return Err(format!(
+                        "{} has {} timestamps for {} frames.",
+                        file.display(),
+                        times.len(),
+                        paths.len()
+                    ));
+                }
+                let first = times[0];
+                times
+                    .iter()
+                    .map(|time| time.saturating_sub(first))
+                    .collect()
+            }
+            None => {
+                if fps <= 0.0 {
+                    return Err("The frame rate must be positive.".to_owned());
+                }
+                (0..paths.len())
+                    .map(|i| Duration::from_secs_f64(i as f64 / fps as f64))
+                    .collect()
+            }
+        };
+        Ok(Self { paths, times })
+    }
+}
+
+/// A decoded frame.
+pub struct Frame {
+    pub width: u32,
+    pub height: u32,
+    pub pixels: Vec<(u8, u8, u8)>,
+}
+
+impl Frame {
+    /// Read a PNG file of any color type.
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let error =
+            |error: png::DecodingError| format!("Unable to read {}: {error}.", path.display());
+        let file = File::open(path)
+            .map_err(|error| format!("Unable to open {}: {error}.", path.display()))?;
+        let mut decoder = png::Decoder::new(file);
+        decoder.set_transformations(png::Transformations::normalize_to_color8());
+        let mut reader = decoder.read_info().map_err(error)?;
+        let mut buffer = vec![0; reader.output_buffer_size()];
+        let info = reader.next_frame(&mut buffer).map_err(error)?;
+        let bytes = &buffer[..info.buffer_size()];
+        let pixels = match info.color_type {
+            png::ColorType::Rgba => rgba_pixels(bytes),
+            png::ColorType::Rgb => bytes
+                .chunks_exact(3)
+                .map(|pixel| (pixel[0], pixel[1], pixel[2]))
+                .collect(),
+            png::ColorType::GrayscaleAlpha => bytes
+                .chunks_exact(2)
+                .map(|pixel| (pixel[0], pixel[0], pixel[0]))
+                .collect(),
+            png::ColorType::Grayscale => bytes.iter().map(|x| (*x, *x, *x)).collect(),
+            png::ColorType::Indexed => {
+                return Err(format!("Unsupported color type in {}.", path.display()))
+            }
+        };
+        Ok(Self {
+            width: info.width,
+            height: info.height,
+            pixels,
+        })
+    }
+
+    /// The pixels of the sensor region, clipped to the frame.
+    pub fn sensor_pixels(&self, sensor: &SensorSettings) -> Result<Vec<(u8, u8, u8)>, String> {
+        let (x, y) = sensor.position;
+        if x >= self.width || y >= self.height {
+            return Err(format!(
+                "The sensor at ({x}, {y}) is outside the {}x{} frame.",
+                self.width, self.height
+            ));
+        }
+        let right = x.saturating_add(sensor.size.0.max(1)).min(self.width);
+        let bottom = y.saturating_add(sensor.size.1.max(1)).min(self.height);
+        Ok((y..bottom)
+            .flat_map(|row| {
+                let start = (row * self.width + x) as usize;
+                self.pixels[start..start + (right - x) as usize]
+                    .iter()
+                    .copied()
+            })
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A new directory with empty files named `files`.
+    fn directory(name: &str, files: &[&str]) -> PathBuf {
+        let directory = std::env::temp_dir().join(format!("morse-{name}-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&directory);
+        std::fs::create_dir_all(&directory).unwrap();
+        for file in files {
+            std::fs::write(directory.join(file), "").unwrap();
+        }
+        directory
+    }
+
+    fn seconds(times: &[Duration]) -> Vec<f64> {
+        times
+            .iter()
+            .map(|time| (time.as_secs_f64() * 1000.0).round() / 1000.0)
+            .collect()
+    }
+
+    #[test]
+    fn frames_in_numeric_order() {
+        let files = [
+            "frame_10.png",
+            "frame_2.PNG",
+            "frame_1.png",
+            "cover.png",
+            "notes.txt",
+        ];
+        let directory = directory("order", &files);
+        let frames = Frames::open(&directory, 4.0, None);
+        std::fs::remove_dir_all(&directory).unwrap();
+        let frames = frames.unwrap();
+        let names: Vec<_> = frames
+            .paths
+            .iter()
+            .map(|path| path.file_name().unwrap().to_string_lossy().into_owned())
+            .collect();
+        assert_eq!(names, ["frame_1.png", "frame_2.PNG", "frame_10.png"]);
+        assert_eq!(seconds(&frames.times), [0.0, 0.25, 0.5]);
+    }
+
+    #[test]
+    fn timestamps_from_the_first() {
+        let directory = directory("timestamps", &["1.png", "2.png", "3.png"]);
+        let timestamps = directory.join("times.txt");
+        let open = |text: &str| {
+            std::fs::write(&timestamps, text).unwrap();
+            Frames::open(&directory, 0.0, Some(&timestamps))
+        };
+        let times = open("1700000000.5\n\n 1700000000.6\n1700000001.25\n");
+        let too_few = open("0.0\n0.1\n");
+        let invalid = open("0.0\n0.1\nlater\n");
+        let negative = open("0.0\n-0.1\n0.2\n");
+        let no_rate = Frames::open(&directory, 0.0, None);
+        std::fs::remove_dir_all(&directory).unwrap();
+
+        assert_eq!(seconds(&times.unwrap().times), [0.0, 0.1, 0.75]);
+        assert!(too_few.is_err());
+        assert!(invalid.is_err());
+        assert!(negative.is_err());
+        assert!(no_rate.is_err());
+    }
+
+    #[test]
+    fn no_frames() {
+        let directory = directory("empty", &["cover.png"]);
+        let frames = Frames::open(&directory, 30.0, None);
+        std::fs::remove_dir_all(&directory).unwrap();
+        assert!(frames.is_err());
+    }
+}
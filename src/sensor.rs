@@ -0,0 +1,568 @@
+//! Classification of sensor colors into on and off states.
+
+use serde::{Deserialize, Serialize};
+
+/// How the pixels of the sensor region are combined into one value.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default, Debug)]
+pub enum Aggregate {
+    /// The value of the mean color.
+    #[default]
+    Mean,
+    /// The value of the per-channel median color.
+    Median,
+    /// The fraction of pixels whose own value is at or past
+    /// [`SensorSettings::pixel_threshold`].
+    Fraction,
+}
+
+/// How a color is turned into a 0-1 value between the off and on colors.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default, Debug)]
+pub enum ColorMode {
+    /// The average position of each RGB channel between the off and on colors. A channel
+    /// the two colors share always contributes 0.
+    #[default]
+    Average,
+    /// The position of the color's luminance between the off and on luminances.
+    Luminance,
+    /// The relative distance to the off and on colors in CIELAB, 0.5 where they are
+    /// equally far. Follows perceived color differences.
+    Lab,
+    /// The projection of the color onto the line from the off to the on color in RGB.
+    Projection,
+    /// The position of the red channel between the off and on colors.
+    Red,
+    /// The position of the green channel between the off and on colors.
+    Green,
+    /// The position of the blue channel between the off and on colors.
+    Blue,
+    /// The relative distance to the hues of the off and on colors, 0.5 where they are
+    /// equally far. Ignores brightness, e.g. for a red and an orange indicator.
+    Hue,
+}
+
+/// The result of reading the sensor region.
+#[derive(Clone, Copy)]
+pub struct Reading {
+    /// Representative color of the region, the median color for [`Aggregate::Median`]
+    /// and the mean color otherwise.
+    pub color: (u8, u8, u8),
+    /// 0-1 value compared against the on threshold.
+    pub value: f32,
+}
+
+/// Where the sensor is and how its color is classified.
+#[derive(Serialize, Deserialize, Clone, Copy)]
+pub struct SensorSettings {
+    /// Pixel position of the top left corner of the sensor region on the screen.
+    #[serde(default)]
+    pub position: (u32, u32),
+    /// Width and height of the sensor region in pixels.
+    #[serde(default = "default_size")]
+    pub size: (u32, u32),
+    /// How the pixels of the region are combined.
+    #[serde(default)]
+    pub aggregate: Aggregate,
+    /// How colors are compared against the off and on colors.
+    #[serde(default)]
+    pub mode: ColorMode,
+    /// Sensor color while the signal is on.
+    pub on_color: (u8, u8, u8),
+    /// Sensor color while the signal is off.
+    pub off_color: (u8, u8, u8),
+    /// Minimum value for the signal to switch on.
+    pub on_threshold: f32,
+    /// Value below which the signal switches back off. Set below
+    /// [`SensorSettings::on_threshold`] for hysteresis, clamped to at most it. [`None`]
+    /// switches off at the on threshold, as in configurations saved before the off
+    /// threshold existed.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub off_threshold: Option<f32>,
+    /// Minimum value of a single pixel to count as on with [`Aggregate::Fraction`], while
+    /// the on and off thresholds apply to the fraction of pixels that are on.
+    #[serde(default = "default_pixel_threshold")]
+    pub pixel_threshold: f32,
+}
+
+impl Default for SensorSettings {
+    fn default() -> Self {
+        Self {
+            position: (0, 0),
+            size: default_size(),
+            aggregate: Aggregate::Mean,
+            mode: ColorMode::Average,
+            on_color: (255, 255, 255),
+            off_color: (255, 255, 255),
+            on_threshold: 0.5,
+            off_threshold: None,
+            pixel_threshold: default_pixel_threshold(),
+        }
+    }
+}
+
+impl SensorSettings {
+    /// The 0-1 position of `color` between the off and on colors according to
+    /// [`SensorSettings::mode`].
+    pub fn value(&self, color: (u8, u8, u8)) -> f32 {
+        let (off, on) = (self.off_color, self.on_color);
+        match self.mode {
+            ColorMode::Average => inverse_lerp3(color, off, on),
+            ColorMode::Luminance => position(luminance(color), luminance(off), luminance(on)),
+            ColorMode::Lab => {
+                let distance = |a: [f32; 3], b: [f32; 3]| {
+                    ((a[0] - b[0]).powi(2) + (a[1] - b[1]).powi(2) + (a[2] - b[2]).powi(2)).sqrt()
+                };
+                let x = lab(color);
+                relative_distance(distance(x, lab(off)), distance(x, lab(on)))
+            }
+            ColorMode::Projection => {
+                let to_f32 = |c: (u8, u8, u8)| [c.0 as f32, c.1 as f32, c.2 as f32];
+                let (x, a, b) = (to_f32(color), to_f32(off), to_f32(on));
+                let axis = [b[0] - a[0], b[1] - a[1], b[2] - a[2]];
+                let length = axis.iter().map(|x| x * x).sum::<f32>();
+                if length == 0.0 {
+                    0.0
+                } else {
+                    let dot = (0..3).map(|i| (x[i] - a[i]) * axis[i]).sum::<f32>();
+                    (dot / length).clamp(0.0, 1.0)
+                }
+            }
+            ColorMode::Red => position(color.0 as f32, off.0 as f32, on.0 as f32),
+            ColorMode::Green => position(color.1 as f32, off.1 as f32, on.1 as f32),
+            ColorMode::Blue => position(color.2 as f32, off.2 as f32, on.2 as f32),
+            ColorMode::Hue => {
+                let distance = |a: f32, b: f32| {
+                    let difference = (a - b).abs() % 360.0;
+                    difference.min(360.0 - difference)
+                };
+                let x = hue(color);
+                relative_distance(distance(x, hue(off)), distance(x, hue(on)))
+            }
+        }
+    }
+
+    /// Whether the pixel `color` is at or past [`SensorSettings::pixel_threshold`].
+    pub fn is_on(&self, color: (u8, u8, u8)) -> bool {
+        self.value(color) >= self.pixel_threshold
+    }
+
+    /// Value below which the signal switches back off, at most the on threshold.
+    pub fn off_threshold(&self) -> f32 {
+        self.off_threshold
+            .unwrap_or(self.on_threshold)
+            .min(self.on_threshold)
+    }
+
+    /// Classify `value` with hysteresis, given whether the signal was on.
+    pub fn classify(&self, value: f32, was_on: bool) -> bool {
+        if was_on {
+            value >= self.off_threshold()
+        } else {
+            value >= self.on_threshold
+        }
+    }
+
+    /// Combine the pixels of the sensor region according to [`SensorSettings::aggregate`].
+    pub fn read(&self, pixels: &[(u8, u8, u8)]) -> Reading {
+        let color = match self.aggregate {
+            Aggregate::Mean | Aggregate::Fraction => mean_color(pixels),
+            Aggregate::Median => median_color(pixels),
+        };
+        let value = match self.aggregate {
+            Aggregate::Mean | Aggregate::Median => self.value(color),
+            Aggregate::Fraction if pixels.is_empty() => 0.0,
+            Aggregate::Fraction => {
+                pixels.iter().filter(|pixel| self.is_on(**pixel)).count() as f32
+                    / pixels.len() as f32
+            }
+        };
+        Reading { color, value }
+    }
+}
+
+/// The result of [`SensorSettings::calibrate`].
+#[derive(Clone, Copy)]
+pub struct Calibration {
+    /// Center of the color cluster taken as on.
+    pub on_color: (u8, u8, u8),
+    /// Center of the color cluster taken as off.
+    pub off_color: (u8, u8, u8),
+    /// Threshold separating the values of the two clusters' colors.
+    pub threshold: f32,
+    /// Fraction of the value variance explained by the threshold (Otsu's effectiveness
+    /// metric). Near 1 the on and off states are cleanly separated, near 0 they overlap.
+    pub separation: f32,
+    /// Fraction of samples classified as on.
+    pub on_fraction: f32,
+}
+
+impl Calibration {
+    /// Describe the separation for display.
+    pub fn quality(&self) -> &'static str {
+        if self.separation >= 0.9 {
+            "good"
+        } else if self.separation >= 0.7 {
+            "fair"
+        } else {
+            "poor"
+        }
+    }
+}
+
+impl SensorSettings {
+    /// Learn the on and off colors and the threshold from colors sampled while the signal
+    /// was both on and off. The colors are clustered into two groups with k-means, and the
+    /// threshold is chosen with Otsu's method. Each group is matched with the nearer of the
+    /// current on and off colors, or the brighter group is taken as on if they are the
+    /// same. Returns [`None`] if there are not two distinct colors.
+    pub fn calibrate(&self, colors: &[(u8, u8, u8)]) -> Option<Calibration> {
+        let to_f32 = |c: &(u8, u8, u8)| [c.0 as f32, c.1 as f32, c.2 as f32];
+        let distance = |a: [f32; 3], b: [f32; 3]| {
+            (a[0] - b[0]).powi(2) + (a[1] - b[1]).powi(2) + (a[2] - b[2]).powi(2)
+        };
+
+        // Cluster the colors, starting from the darkest and brightest
+        let darkest = colors
+            .iter()
+            .min_by(|a, b| luminance(**a).total_cmp(&luminance(**b)))?;
+        let brightest = colors
+            .iter()
+            .max_by(|a, b| luminance(**a).total_cmp(&luminance(**b)))?;
+        let mut centers = [to_f32(darkest), to_f32(brightest)];
+        for _ in 0..16 {
+            let mut sums = [([0.0; 3], 0); 2];
+            for color in colors.iter().map(to_f32) {
+                let nearest = (distance(color, centers[1]) < distance(color, centers[0])) as usize;
+                for (sum, x) in sums[nearest].0.iter_mut().zip(color) {
+                    *sum += x;
+                }
+                sums[nearest].1 += 1;
+            }
+            for (center, (sum, count)) in centers.iter_mut().zip(sums) {
+                if count > 0 {
+                    *center = sum.map(|x| x / count as f32);
+                }
+            }
+        }
+        if distance(centers[0], centers[1]).sqrt() < 8.0 {
+            return None;
+        }
+        let (on, off) = (to_f32(&self.on_color), to_f32(&self.off_color));
+        if on != off
+            && distance(centers[0], on) + distance(centers[1], off)
+                < distance(centers[0], off) + distance(centers[1], on)
+        {
+            centers.swap(0, 1);
+        }
+        let to_u8 = |c: [f32; 3]| (c[0].round() as u8, c[1].round() as u8, c[2].round() as u8);
+        let (off_color, on_color) = (to_u8(centers[0]), to_u8(centers[1]));
+
+        // Otsu's method on the values of the colors under the new settings
+        let settings = Self {
+            on_color,
+            off_color,
+            ..*self
+        };
+        let mut values: Vec<f32> = colors.iter().map(|c| settings.value(*c)).collect();
+        values.sort_by(f32::total_cmp);
+        let n = values.len() as f32;
+        let total: f32 = values.iter().sum();
+        let mean = total / n;
+        let variance = values.iter().map(|x| (x - mean).powi(2)).sum::<f32>() / n;
+        let mut best = (0.0, 0.5, 0.0);
+        let mut below = 0.0;
+        for i in 1..values.len() {
+            below += values[i - 1];
+            if values[i] == values[i - 1] {
+                continue;
+            }
+            let w0 = i as f32 / n;
+            let m0 = below / i as f32;
+            let m1 = (total - below) / (n - i as f32);
+            let between = w0 * (1.0 - w0) * (m1 - m0).powi(2);
+            if between > best.0 {
+                best = (between, (values[i - 1] + values[i]) / 2.0, 1.0 - w0);
+            }
+        }
+        let (between, threshold, on_fraction) = best;
+
+        Some(Calibration {
+            on_color,
+            off_color,
+            threshold,
+            separation: if variance > 0.0 {
+                between / variance
+            } else {
+                0.0
+            },
+            on_fraction,
+        })
+    }
+
+    /// Use the calibrated colors and threshold, keeping the hysteresis between the
+    /// on and off thresholds. With [`Aggregate::Fraction`], the threshold is a color value
+    /// and becomes the [`SensorSettings::pixel_threshold`] instead.
+    pub fn apply_calibration(&mut self, calibration: &Calibration) {
+        self.on_color = calibration.on_color;
+        self.off_color = calibration.off_color;
+        if self.aggregate == Aggregate::Fraction {
+            self.pixel_threshold = calibration.threshold;
+            return;
+        }
+        let hysteresis = self.on_threshold - self.off_threshold();
+        self.on_threshold = calibration.threshold;
+        if self.off_threshold.is_some() {
+            self.off_threshold = Some((calibration.threshold - hysteresis).max(0.0));
+        }
+    }
+}
+
+/// Relative luminance (0-1) of an sRGB color, ignoring gamma.
+pub fn luminance(color: (u8, u8, u8)) -> f32 {
+    (0.2126 * color.0 as f32 + 0.7152 * color.1 as f32 + 0.0722 * color.2 as f32) / 255.0
+}
+
+/// The clamped 0-1 position of `x` from `off` to `on`, which may be the larger or smaller.
+fn position(x: f32, off: f32, on: f32) -> f32 {
+    if off == on {
+        0.0
+    } else {
+        ((x - off) / (on - off)).clamp(0.0, 1.0)
+    }
+}
+
+/// 0 at the off color, 1 at the on color and 0.5 where both are equally far.
+fn relative_distance(off: f32, on: f32) -> f32 {
+    if off + on == 0.0 {
+        0.0
+    } else {
+        off / (off + on)
+    }
+}
+
+/// CIELAB coordinates (L*, a*, b*) of an sRGB color under the D65 white point.
+pub fn lab(color: (u8, u8, u8)) -> [f32; 3] {
+    let linear = |x: u8| {
+        let x = x as f32 / 255.0;
+        if x <= 0.04045 {
+            x / 12.92
+        } else {
+            ((x + 0.055) / 1.055).powf(2.4)
+        }
+    };
+    let (r, g, b) = (linear(color.0), linear(color.1), linear(color.2));
+    let x = (0.4124 * r + 0.3576 * g + 0.1805 * b) / 0.95047;
+    let y = 0.2126 * r + 0.7152 * g + 0.0722 * b;
+    let z = (0.0193 * r + 0.1192 * g + 0.9505 * b) / 1.08883;
+    let f = |t: f32| {
+        if t > 216.0 / 24389.0 {
+            t.cbrt()
+        } else {
+            (24389.0 / 27.0 * t + 16.0) / 116.0
+        }
+    };
+    let (x, y, z) = (f(x), f(y), f(z));
+    [116.0 * y - 16.0, 500.0 * (x - y), 200.0 * (y - z)]
+}
+
+/// Hue of a color in degrees (0-360), 0 for grays.
+pub fn hue(color: (u8, u8, u8)) -> f32 {
+    let (r, g, b) = (color.0 as f32, color.1 as f32, color.2 as f32);
+    let max = r.max(g).max(b);
+    let chroma = max - r.min(g).min(b);
+    let hue = if chroma == 0.0 {
+        0.0
+    } else if max == r {
+        (g - b) / chroma
+    } else if max == g {
+        (b - r) / chroma + 2.0
+    } else {
+        (r - g) / chroma + 4.0
+    };
+    (hue * 60.0).rem_euclid(360.0)
+}
+
+fn default_size() -> (u32, u32) {
+    (1, 1)
+}
+
+fn default_pixel_threshold() -> f32 {
+    0.5
+}
+
+/// Convert RGBA bytes into RGB pixels.
+pub fn rgba_pixels(rgba: &[u8]) -> Vec<(u8, u8, u8)> {
+    rgba.chunks_exact(4)
+        .map(|pixel| (pixel[0], pixel[1], pixel[2]))
+        .collect()
+}
+
+/// The per-channel mean of `pixels`, black if there are none.
+pub fn mean_color(pixels: &[(u8, u8, u8)]) -> (u8, u8, u8) {
+    if pixels.is_empty() {
+        return (0, 0, 0);
+    }
+    let sum = pixels
+        .iter()
+        .fold((0, 0, 0), |sum: (usize, usize, usize), pixel| {
+            (
+                sum.0 + pixel.0 as usize,
+                sum.1 + pixel.1 as usize,
+                sum.2 + pixel.2 as usize,
+            )
+        });
+    let n = pixels.len();
+    ((sum.0 / n) as u8, (sum.1 / n) as u8, (sum.2 / n) as u8)
+}
+
+/// The per-channel median of `pixels`, black if there are none.
+pub fn median_color(pixels: &[(u8, u8, u8)]) -> (u8, u8, u8) {
+    if pixels.is_empty() {
+        return (0, 0, 0);
+    }
+    let median = |channel: fn(&(u8, u8, u8)) -> u8| {
+        let mut values: Vec<u8> = pixels.iter().map(channel).collect();
+        values.sort_unstable();
+        values[values.len() / 2]
+    };
+    (median(|x| x.0), median(|x| x.1), median(|x| x.2))
+}
+
+/// Linearly interpolate from the smaller to the larger of `a` and `b`, clamping `x` to 0-1.
+pub fn lerp(x: f32, a: u8, b: u8) -> u8 {
+    let min = a.min(b);
+    let max = a.max(b);
+    if x <= 0.0 {
+        min
+    } else if x >= 1.0 {
+        max
+    } else {
+        ((max - min) as f32 * x) as u8 + min
+    }
+}
+
+/// [`lerp`] each channel of a color.
+pub fn lerp3(x: f32, a: (u8, u8, u8), b: (u8, u8, u8)) -> (u8, u8, u8) {
+    (lerp(x, a.0, b.0), lerp(x, a.1, b.1), lerp(x, a.2, b.2))
+}
+
+/// The clamped 0-1 position of `x` from the smaller to the larger of `a` and `b`.
+pub fn inverse_lerp(x: u8, a: u8, b: u8) -> f32 {
+    let min = a.min(b);
+    let max = a.max(b);
+    if x <= min {
+        0.0
+    } else if x >= max {
+        1.0
+    } else {
+        (x - min) as f32 / (max - min) as f32
+    }
+}
+
+/// The average [`inverse_lerp`] of each channel of a color.
+pub fn inverse_lerp3(x: (u8, u8, u8), a: (u8, u8, u8), b: (u8, u8, u8)) -> f32 {
+    (inverse_lerp(x.0, a.0, b.0) + inverse_lerp(x.1, a.1, b.1) + inverse_lerp(x.2, a.2, b.2)) / 3.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// An orange indicator that is red while off. Both colors have no blue.
+    fn indicator(mode: ColorMode) -> SensorSettings {
+        SensorSettings {
+            mode,
+            on_color: (255, 160, 0),
+            off_color: (200, 40, 0),
+            ..SensorSettings::default()
+        }
+    }
+
+    #[test]
+    fn color_modes() {
+        let modes = [
+            ColorMode::Luminance,
+            ColorMode::Lab,
+            ColorMode::Projection,
+            ColorMode::Red,
+            ColorMode::Green,
+            ColorMode::Hue,
+        ];
+        for mode in modes {
+            let settings = indicator(mode);
+            assert!(settings.value(settings.off_color) < 0.01, "{mode:?}");
+            assert!(settings.value(settings.on_color) > 0.99, "{mode:?}");
+            let between = settings.value((228, 100, 0));
+            assert!((0.3..0.7).contains(&between), "{mode:?}: {between}");
+        }
+
+        // A channel the colors share contributes nothing
+        let on = (255, 160, 0);
+        assert!((indicator(ColorMode::Average).value(on) - 2.0 / 3.0).abs() < 1e-6);
+        assert_eq!(indicator(ColorMode::Blue).value(on), 0.0);
+
+        // Hue ignores brightness
+        assert!(indicator(ColorMode::Hue).value((100, 63, 0)) > 0.99);
+        assert!(indicator(ColorMode::Luminance).value((100, 63, 0)) < 0.01);
+    }
+
+    #[test]
+    fn fraction_of_pixels() {
+        let settings = SensorSettings {
+            aggregate: Aggregate::Fraction,
+            pixel_threshold: 0.8,
+            ..indicator(ColorMode::Projection)
+        };
+        let pixels = [(255, 160, 0), (250, 150, 0), (228, 100, 0), (200, 40, 0)];
+        assert_eq!(settings.read(&pixels).value, 0.5);
+        assert_eq!(settings.read(&[]).value, 0.0);
+    }
+
+    /// A signal that alternates between `on` and `off`.
+    fn flashing(on: (u8, u8, u8), off: (u8, u8, u8)) -> Vec<(u8, u8, u8)> {
+        (0..100)
+            .map(|i| if i % 3 == 0 { on } else { off })
+            .collect()
+    }
+
+    #[test]
+    fn calibration_orientation() {
+        let (dark, light) = ((10, 10, 10), (240, 240, 240));
+        let colors = flashing(dark, light);
+
+        // Without distinct colors the brighter cluster is on
+        let calibration = SensorSettings::default().calibrate(&colors).unwrap();
+        assert_eq!((calibration.on_color, calibration.off_color), (light, dark));
+        assert!((calibration.on_fraction - 0.66).abs() < 0.01);
+
+        // A signal that turns dark when on
+        let settings = SensorSettings {
+            mode: ColorMode::Projection,
+            on_color: (0, 0, 0),
+            off_color: (255, 255, 255),
+            ..SensorSettings::default()
+        };
+        let calibration = settings.calibrate(&colors).unwrap();
+        assert_eq!((calibration.on_color, calibration.off_color), (dark, light));
+        assert!((calibration.on_fraction - 0.34).abs() < 0.01);
+        assert!(calibration.separation > 0.9);
+    }
+
+    #[test]
+    fn calibration_of_fraction() {
+        let mut settings = SensorSettings {
+            aggregate: Aggregate::Fraction,
+            on_threshold: 0.3,
+            off_threshold: Some(0.2),
+            ..SensorSettings::default()
+        };
+        let calibration = settings
+            .calibrate(&flashing((250, 0, 0), (250, 120, 0)))
+            .unwrap();
+        settings.apply_calibration(&calibration);
+        assert_eq!(settings.pixel_threshold, calibration.threshold);
+        assert_eq!(
+            (settings.on_threshold, settings.off_threshold),
+            (0.3, Some(0.2))
+        );
+    }
+}
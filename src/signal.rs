@@ -0,0 +1,38 @@
+//! Sources of timestamped on/off samples, such as the screen or a recording.
+
+use std::time::Duration;
+
+use crate::{
+    audio::ToneSettings,
+    sensor::{Reading, SensorSettings},
+};
+
+/// What a [`SignalSource`] samples for one channel.
+#[derive(Clone, Copy)]
+pub struct SourceChannel {
+    /// Sensor region and color classification. Sources without pixels still use the
+    /// off and on colors for their readings.
+    pub sensor: SensorSettings,
+    /// Tone of the channel in audio.
+    pub tone: ToneSettings,
+}
+
+/// One sample of every channel.
+pub struct Sample {
+    /// Time of the sample. Live sources measure it from the capture epoch, recorded
+    /// sources from the start of the recording.
+    pub time: Duration,
+    /// Classification and reading of each channel.
+    pub readings: Vec<(bool, Reading)>,
+}
+
+/// Produces timestamped on/off samples for the decoders, such as the screen or a recording.
+pub trait SignalSource: Send {
+    /// Whether samples are taken in real time. Live sources are paced to the sample rate,
+    /// while recorded sources can be played back at their recorded speed or read as fast
+    /// as possible.
+    fn live(&self) -> bool;
+
+    /// Take the next sample of every channel, or [`None`] once the source has ended.
+    fn next_sample(&mut self, channels: &[SourceChannel]) -> Result<Option<Sample>, String>;
+}
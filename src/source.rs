@@ -0,0 +1,308 @@
+use std::time::{Duration, Instant};
+
+use morse::{
+    alphabet::Alphabet,
+    audio::{read_wav, ToneDetector, ToneSettings},
+    morse_encoder::{EncoderSettings, Schedule},
+    sensor::{lerp3, Reading},
+    signal::{Sample, SignalSource, SourceChannel},
+};
+use screenshots::Screen;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    capture_sensor,
+    replay::{Frame, Frames},
+};
+
+/// Which source is sampled, as saved in the configuration.
+#[derive(Serialize, Deserialize, Clone, PartialEq, Default, Debug)]
+#[serde(tag = "kind")]
+pub enum SourceSettings {
+    /// The sensor region of each channel on the selected screen.
+    #[default]
+    Screen,
+    /// The sensor region of each channel in a directory of numbered PNG frames.
+    Frames {
+        directory: String,
+        /// Frames per second, unless timed by `timestamps`.
+        fps: f32,
+        /// File with the time of each frame in seconds, empty to use `fps`.
+        #[serde(default)]
+        timestamps: String,
+    },
+    /// The tone of each channel in a WAV file.
+    Audio { path: String },
+    /// Text sent repeatedly at a fixed speed, for testing without a signal.
+    Synthetic { text: String, wpm: f32 },
+}
+
+impl SourceSettings {
+    /// Open the source. `screen` is only required for [`SourceSettings::Screen`].
+    pub fn open(
+        &self,
+        screen: Option<Screen>,
+        epoch: Instant,
+    ) -> Result<Box<dyn SignalSource>, String> {
+        Ok(match self {
+            Self::Screen => Box::new(ScreenSource {
+                screen: screen.ok_or("No screen selected.")?,
+                epoch,
+                last_on: Vec::new(),
+            }),
+            Self::Frames {
+                directory,
+                fps,
+                timestamps,
+            } => {
+                let timestamps = (!timestamps.is_empty()).then_some(timestamps.as_ref());
+                let frames = Frames::open(directory.as_ref(), *fps, timestamps)?;
+                eprintln!(
+                    "[Source] Replaying {} frames over {:.1} s.",
+                    frames.paths.len(),
+                    frames.times.last().map_or(0.0, Duration::as_secs_f32)
+                );
+                Box::new(FramesSource {
+                    frames,
+                    index: 0,
+                    last_on: Vec::new(),
+                })
+            }
+            Self::Audio { path } => {
+                let (samples, sample_rate) = read_wav(path.as_ref())?;
+                eprintln!(
+                    "[Source] Decoding {path}, {:.1} s at {sample_rate} Hz.",
+                    samples.len() as f32 / sample_rate as f32
+                );
+                Box::new(AudioSource {
+                    samples,
+                    sample_rate,
+                    position: 0,
+                    detectors: Vec::new(),
+                    readings: Vec::new(),
+                })
+            }
+            Self::Synthetic { text, wpm } => Box::new(SyntheticSource::new(text, *wpm, epoch)?),
+        })
+    }
+}
+
+/// Classify `reading` with the channel's hysteresis and remember the result.
+fn classify(channel: &SourceChannel, reading: Reading, last_on: &mut bool) -> (bool, Reading) {
+    *last_on = channel.sensor.classify(reading.value, *last_on);
+    (*last_on, reading)
+}
+
+/// Samples the sensor region of each channel on a screen.
+pub struct ScreenSource {
+    screen: Screen,
+    epoch: Instant,
+    last_on: Vec<bool>,
+}
+
+impl SignalSource for ScreenSource {
+    fn live(&self) -> bool {
+        true
+    }
+
+    fn next_sample(&mut self, channels: &[SourceChannel]) -> Result<Option<Sample>, String> {
+        self.last_on.resize(channels.len(), false);
+        let mut readings = Vec::with_capacity(channels.len());
+        for (channel, last_on) in channels.iter().zip(&mut self.last_on) {
+            let pixels = capture_sensor(&channel.sensor, &self.screen)?;
+            readings.push(classify(channel, channel.sensor.read(&pixels), last_on));
+        }
+        Ok(Some(Sample {
+            time: self.epoch.elapsed(),
+            readings,
+        }))
+    }
+}
+
+/// Reads the sensor region of each channel from recorded frames.
+pub struct FramesSource {
+    frames: Frames,
+    index: usize,
+    last_on: Vec<bool>,
+}
+
+impl SignalSource for FramesSource {
+    fn live(&self) -> bool {
+        false
+    }
+
+    fn next_sample(&mut self, channels: &[SourceChannel]) -> Result<Option<Sample>, String> {
+        let Some(path) = self.frames.paths.get(self.index) else {
+            return Ok(None);
+        };
+        let time = self.frames.times[self.index];
+        self.index += 1;
+
+        let frame = Frame::load(path)?;
+        self.last_on.resize(channels.len(), false);
+        let mut readings = Vec::with_capacity(channels.len());
+        for (channel, last_on) in channels.iter().zip(&mut self.last_on) {
+            let pixels = frame.sensor_pixels(&channel.sensor).map_err(|error| {
+                format!("{} ({}).", error.trim_end_matches('.'), path.display())
+            })?;
+            readings.push(classify(channel, channel.sensor.read(&pixels), last_on));
+        }
+        Ok(Some(Sample { time, readings }))
+    }
+}
+
+/// Detects the tone of each channel in recorded audio.
+pub struct AudioSource {
+    samples: Vec<f32>,
+    sample_rate: u32,
+    position: usize,
+    /// Detector of each channel and the settings it was created with.
+    detectors: Vec<(ToneSettings, ToneDetector)>,
+    /// Latest reading of each channel.
+    readings: Vec<(bool, Reading)>,
+}
+
+impl SignalSource for AudioSource {
+    fn live(&self) -> bool {
+        false
+    }
+
+    fn next_sample(&mut self, channels: &[SourceChannel]) -> Result<Option<Sample>, String> {
+        if self.position >= self.samples.len() {
+            return Ok(None);
+        }
+
+        // Restart the detectors of channels whose tone settings changed
+        self.detectors.truncate(channels.len());
+        for (i, channel) in channels.iter().enumerate() {
+            match self.detectors.get_mut(i) {
+                Some((tone, _)) if *tone == channel.tone => {}
+                Some(detector) => {
+                    *detector = (
+                        channel.tone,
+                        ToneDetector::new(channel.tone, self.sample_rate),
+                    )
+                }
+                None => self.detectors.push((
+                    channel.tone,
+                    ToneDetector::new(channel.tone, self.sample_rate),
+                )),
+            }
+        }
+
+        // Advance by the shortest block
+        let step = self
+            .detectors
+            .iter()
+            .map(|(_, detector)| detector.block_length())
+            .min()
+            .unwrap_or(1);
+        let end = (self.position + step).min(self.samples.len());
+        let block = &self.samples[self.position..end];
+        self.position = end;
+
+        // The level is shown as a color between the channel's off and on colors.
+        self.readings.resize(
+            channels.len(),
+            (
+                false,
+                Reading {
+                    color: (0, 0, 0),
+                    value: 0.0,
+                },
+            ),
+        );
+        for ((channel, (_, detector)), reading) in channels
+            .iter()
+            .zip(&mut self.detectors)
+            .zip(&mut self.readings)
+        {
+            if let Some(tone) = detector.process(block).last() {
+                *reading = (
+                    tone.on,
+                    Reading {
+                        color: lerp3(
+                            tone.level,
+                            channel.sensor.off_color,
+                            channel.sensor.on_color,
+                        ),
+                        value: tone.level,
+                    },
+                );
+            }
+        }
+        Ok(Some(Sample {
+            time: Duration::from_secs_f64(self.position as f64 / self.sample_rate as f64),
+            readings: self.readings.clone(),
+        }))
+    }
+}
+
+/// Generates a text in Morse code, repeated forever.
+pub struct SyntheticSource {
+    /// One repetition, ending with a word gap.
+    schedule: Schedule,
+    period: Duration,
+    epoch: Instant,
+    start: Duration,
+}
+
+impl SyntheticSource {
+    /// Send `text` at `wpm` words per minute (PARIS).
+    /// Characters without a Morse code are skipped.
+    pub fn new(text: &str, wpm: f32, epoch: Instant) -> Result<Self, String> {
+        if wpm <= 0.0 {
+            return Err("The speed must be positive.".to_owned());
+        }
+        let settings = EncoderSettings {
+            wpm,
+            farnsworth_wpm: wpm,
+        };
+        let mut schedule = Schedule::from_text(text, &Alphabet::latin(), &settings);
+        if schedule.elements.is_empty() {
+            return Err("The text has no characters to send.".to_owned());
+        }
+        let (_, word_gap) = settings.gaps();
+        schedule
+            .elements
+            .push((false, Duration::from_secs_f32(word_gap / 1000.0)));
+        Ok(Self {
+            period: schedule.duration(),
+            schedule,
+            epoch,
+            start: epoch.elapsed(),
+        })
+    }
+}
+
+impl SignalSource for SyntheticSource {
+    fn live(&self) -> bool {
+        true
+    }
+
+    fn next_sample(&mut self, channels: &[SourceChannel]) -> Result<Option<Sample>, String> {
+        let time = self.epoch.elapsed();
+        let position =
+            Duration::from_secs_f64((time - self.start).as_secs_f64() % self.period.as_secs_f64());
+        let on = self.schedule.is_on(position);
+        let readings = channels
+            .iter()
+            .map(|channel| {
+                let sensor = &channel.sensor;
+                let color = if on {
+                    sensor.on_color
+                } else {
+                    sensor.off_color
+                };
+                (
+                    on,
+                    Reading {
+                        color,
+                        value: if on { 1.0 } else { 0.0 },
+                    },
+                )
+            })
+            .collect();
+        Ok(Some(Sample { time, readings }))
+    }
+}
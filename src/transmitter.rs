@@ -0,0 +1,171 @@
+use std::time::{Duration, Instant};
+
+use egui::{Color32, DragValue, Vec2};
+use morse::{
+    audio::SynthesisSettings,
+    morse_encoder::{EncoderSettings, Schedule},
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{channel::Channel, save_audio};
+
+/// What the transmitter sends and how.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct TransmitterSettings {
+    pub text: String,
+    pub encoder: EncoderSettings,
+    /// Send the text again after a word gap once it has finished.
+    pub repeat: bool,
+    /// Width and height of the flashing panel in points.
+    pub size: f32,
+    /// Tone of exported audio.
+    #[serde(default)]
+    pub audio: SynthesisSettings,
+}
+
+impl Default for TransmitterSettings {
+    fn default() -> Self {
+        Self {
+            text: "PARIS".to_owned(),
+            encoder: EncoderSettings::default(),
+            repeat: false,
+            size: 200.0,
+            audio: SynthesisSettings::default(),
+        }
+    }
+}
+
+/// Flashes a panel between the on and off colors to send text as light.
+pub struct Transmitter {
+    pub settings: TransmitterSettings,
+    /// Signal being sent and when it started.
+    sending: Option<(Schedule, Instant)>,
+}
+
+impl Transmitter {
+    pub fn new(settings: TransmitterSettings) -> Self {
+        Self {
+            settings,
+            sending: None,
+        }
+    }
+
+    /// Show the transmitter settings and the panel, and request a repaint at the next
+    /// change of the signal. The text is sent in the colors and alphabet of `channel`.
+    /// Returns an error if exporting audio failed.
+    pub fn ui(&mut self, ui: &mut egui::Ui, channel: &Channel) -> Result<(), String> {
+        let (on_color, off_color) = (
+            channel.settings.sensor.on_color,
+            channel.settings.sensor.off_color,
+        );
+        let mut result = Ok(());
+        let settings = &mut self.settings;
+        ui.add_enabled_ui(self.sending.is_none(), |ui| {
+            ui.add(egui::TextEdit::multiline(&mut settings.text).desired_rows(2));
+            egui::Grid::new("transmitter settings").show(ui, |ui| {
+                ui.label("Speed (WPM)");
+                ui.add(DragValue::new(&mut settings.encoder.wpm).clamp_range(1.0..=100.0));
+                ui.end_row();
+                ui.label("Farnsworth Speed (WPM)");
+                ui.add(
+                    DragValue::new(&mut settings.encoder.farnsworth_wpm)
+                        .clamp_range(1.0..=settings.encoder.wpm),
+                );
+                ui.end_row();
+                ui.label("Repeat");
+                ui.checkbox(&mut settings.repeat, "");
+            });
+        });
+        ui.horizontal(|ui| {
+            ui.label("Panel Size");
+            ui.add(DragValue::new(&mut settings.size).clamp_range(10.0..=2000.0));
+        });
+
+        if ui
+            .button(if self.sending.is_some() {
+                "Stop Transmitting"
+            } else {
+                "Start Transmitting"
+            })
+            .clicked()
+        {
+            self.sending = match self.sending {
+                Some(_) => None,
+                None => {
+                    let mut schedule = channel.schedule(&settings.text, &settings.encoder);
+                    let (_, word_gap) = settings.encoder.gaps();
+                    schedule
+                        .elements
+                        .push((false, Duration::from_secs_f32(word_gap / 1000.0)));
+                    Some((schedule, Instant::now()))
+                }
+            };
+        }
+
+        // Find the state of the signal
+        let mut on = false;
+        if let Some((schedule, start)) = &self.sending {
+            let duration = schedule.duration();
+            let mut elapsed = start.elapsed();
+            if settings.repeat && !duration.is_zero() {
+                elapsed = Duration::from_secs_f64(elapsed.as_secs_f64() % duration.as_secs_f64());
+            }
+            if elapsed >= duration {
+                self.sending = None;
+            } else {
+                on = schedule.is_on(elapsed);
+
+                // Repaint as soon as the signal changes
+                let mut end = Duration::ZERO;
+                for (_, duration) in &schedule.elements {
+                    end += *duration;
+                    if end > elapsed {
+                        ui.ctx().request_repaint_after(end - elapsed);
+                        break;
+                    }
+                }
+            }
+        }
+        ui.label(if self.sending.is_some() {
+            "Transmitting"
+        } else {
+            "Idle"
+        });
+
+        // Panel
+        let color = if on { on_color } else { off_color };
+        let (response, painter) =
+            ui.allocate_painter(Vec2::splat(settings.size), egui::Sense::hover());
+        painter.rect_filled(
+            response.rect,
+            0.0,
+            Color32::from_rgb(color.0, color.1, color.2),
+        );
+
+        // Audio export
+        egui::CollapsingHeader::new("Audio Export").show(ui, |ui| {
+            let audio = &mut settings.audio;
+            egui::Grid::new("audio export settings").show(ui, |ui| {
+                ui.label("Tone (Hz)");
+                ui.add(DragValue::new(&mut audio.frequency).clamp_range(20.0..=20000.0));
+                ui.end_row();
+                ui.label("Sample Rate (Hz)");
+                ui.add(DragValue::new(&mut audio.sample_rate).clamp_range(4000..=96000));
+                ui.end_row();
+                ui.label("Amplitude");
+                ui.add(egui::Slider::new(&mut audio.amplitude, 0.0..=1.0));
+                ui.end_row();
+                ui.label("Rise/Fall (ms)");
+                ui.add(DragValue::new(&mut audio.rise).clamp_range(0.0..=50.0));
+                ui.end_row();
+                ui.label("Noise");
+                ui.add(egui::Slider::new(&mut audio.noise, 0.0..=1.0));
+            });
+            if ui.button("Export morse.wav").clicked() {
+                let schedule = channel.schedule(&settings.text, &settings.encoder);
+                result = save_audio("morse.wav", &schedule, &settings.audio).map(|_| ());
+            }
+        });
+        result
+    }
+}